@@ -0,0 +1,157 @@
+use crate::cache::{is_cached, load_from_cache, save_to_cache};
+use crate::calendar::calendar_name;
+use crate::config::{AgendaConfig, AgendaPeriod, Config};
+use crate::notification::push_agenda_notifications;
+use crate::recurrence::occurrences;
+
+use anyhow::{Context, Result, bail};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+};
+use chrono_tz::Tz;
+use icalendar::{Calendar, Component, EventLike};
+use std::collections::HashMap;
+
+static AGENDA_STATE_FILE: &str = "agenda.json";
+
+/// Upcoming events of a filtered calendar
+pub struct Agenda {
+    pub calendar_name: String,
+    pub entries: Vec<AgendaEntry>,
+    pub title: String,
+}
+
+pub struct AgendaEntry {
+    pub all_day: bool,
+    pub end: DateTime<FixedOffset>,
+    pub location: Option<String>,
+    pub start: DateTime<FixedOffset>,
+    pub summary: String,
+}
+
+/// First and last day covered by the agenda and its description
+fn agenda_range(period: AgendaPeriod, today: NaiveDate) -> (NaiveDate, NaiveDate, &'static str) {
+    match period {
+        AgendaPeriod::NextWeek => {
+            let monday =
+                today + Duration::days(7 - i64::from(today.weekday().num_days_from_monday()));
+            (monday, monday + Duration::days(6), "next week")
+        }
+        AgendaPeriod::Today => (today, today, "today"),
+        AgendaPeriod::Tomorrow => {
+            let tomorrow = today + Duration::days(1);
+            (tomorrow, tomorrow, "tomorrow")
+        }
+    }
+}
+
+fn build_agenda(name: &str, config: &AgendaConfig, today: NaiveDate) -> Result<Agenda> {
+    let data = load_from_cache(&format!("{name}_filtered.ics"))?;
+
+    // Not using with_context() because "the trait bound `std::string::String: StdError` is not satisfied"
+    let calendar: Calendar = match data.parse() {
+        Ok(data) => data,
+        Err(e) => bail!("Failed to parse calendar '{name}'!\n{e}"),
+    };
+
+    let (first_day, last_day, description) = agenda_range(config.period.unwrap_or_default(), today);
+    let from = start_of_day(first_day, config.timezone)?;
+    let until = start_of_day(last_day + Duration::days(1), config.timezone)?;
+
+    let to_offset = |date_time: DateTime<Local>| match config.timezone {
+        Some(tz) => date_time.with_timezone(&tz).fixed_offset(),
+        None => date_time.fixed_offset(),
+    };
+
+    let entries = occurrences(&calendar, from, until)
+        .into_iter()
+        .map(|occurrence| AgendaEntry {
+            all_day: occurrence.all_day,
+            end: to_offset(occurrence.end),
+            location: occurrence.event.get_location().map(String::from),
+            start: to_offset(occurrence.start),
+            summary: occurrence
+                .event
+                .get_summary()
+                .unwrap_or_default()
+                .to_string(),
+        })
+        .collect();
+
+    Ok(Agenda {
+        calendar_name: name.to_string(),
+        entries,
+        title: format!("'{name}' -- Agenda for {description}"),
+    })
+}
+
+fn load_agenda_state() -> Result<HashMap<String, NaiveDate>> {
+    if !is_cached(AGENDA_STATE_FILE) {
+        return Ok(HashMap::new());
+    }
+
+    let data = load_from_cache(AGENDA_STATE_FILE)?;
+    serde_json::from_str(&data).with_context(|| "Failed to parse agenda state!")
+}
+
+fn now_in(timezone: Option<Tz>) -> NaiveDateTime {
+    match timezone {
+        Some(tz) => Local::now().with_timezone(&tz).naive_local(),
+        None => Local::now().naive_local(),
+    }
+}
+
+/// Sends the agendas whose send time passed today and that were not sent yet
+pub fn push_agendas(config: &Config) -> Result<()> {
+    let mut state = load_agenda_state()?;
+    let mut agendas = Vec::new();
+
+    for calendar in &config.calendars {
+        let Some(agenda_config) = &calendar.agenda else {
+            continue;
+        };
+
+        let name = calendar_name(calendar)?;
+        let now = now_in(agenda_config.timezone);
+        let today = now.date();
+
+        if now.time() < agenda_config.send_at
+            || state.get(&name) == Some(&today)
+            || agenda_config
+                .weekday
+                .is_some_and(|weekday| weekday != today.weekday())
+        {
+            continue;
+        }
+
+        agendas.push(build_agenda(&name, agenda_config, today)?);
+        state.insert(name, today);
+    }
+
+    if agendas.is_empty() {
+        return Ok(());
+    }
+
+    println!(
+        "[{}] Sending agendas.",
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+    push_agenda_notifications(config, &agendas)?;
+
+    let data = serde_json::to_string_pretty(&state)
+        .with_context(|| "Failed to serialize agenda state!")?;
+    save_to_cache(&data, AGENDA_STATE_FILE)
+}
+
+fn start_of_day(date: NaiveDate, timezone: Option<Tz>) -> Result<DateTime<Local>> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    let start = match timezone {
+        Some(tz) => tz
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(|date_time| date_time.with_timezone(&Local)),
+        None => Local.from_local_datetime(&midnight).earliest(),
+    };
+
+    start.with_context(|| format!("Failed to determine the start of {date}"))
+}
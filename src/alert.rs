@@ -0,0 +1,204 @@
+use crate::cache::{is_cached, load_from_cache, save_to_cache};
+use crate::config::Config;
+use crate::notification::push_alert_notification;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+
+static ALERT_STATE_FILE: &str = "alerts.json";
+
+/// Failure of a run or the recovery from it
+pub struct Alert {
+    /// Error chain, one cause per line
+    pub error: String,
+    pub recovered: bool,
+    pub since: DateTime<Local>,
+}
+
+/// Failure that was alerted and did not recover yet
+#[derive(Deserialize, Serialize)]
+struct AlertState {
+    error: String,
+    last_alert: DateTime<Local>,
+    since: DateTime<Local>,
+}
+
+fn load_alert_state() -> Result<Option<AlertState>> {
+    if !is_cached(ALERT_STATE_FILE) {
+        return Ok(None);
+    }
+
+    let data = load_from_cache(ALERT_STATE_FILE)?;
+    serde_json::from_str(&data).with_context(|| "Failed to parse alert state!")
+}
+
+/// Alert due for the result of a run and the failure state to keep after sending it, `None` if
+/// the run neither failed with a new error nor recovered
+fn next_alert(
+    state: Option<AlertState>,
+    error: Option<String>,
+    repeat_hours: Option<u32>,
+    now: DateTime<Local>,
+) -> Option<(Alert, Option<AlertState>)> {
+    match (error, state) {
+        (None, None) => None,
+        (None, Some(state)) => Some((
+            Alert {
+                error: state.error,
+                recovered: true,
+                since: state.since,
+            },
+            None,
+        )),
+        (Some(error), state) => {
+            let since = state.as_ref().map_or(now, |state| state.since);
+            let due = match &state {
+                Some(state) if state.error == error => repeat_hours.is_some_and(|hours| {
+                    now - state.last_alert >= Duration::hours(i64::from(hours))
+                }),
+                _ => true,
+            };
+
+            due.then(|| {
+                (
+                    Alert {
+                        error: error.clone(),
+                        recovered: false,
+                        since,
+                    },
+                    Some(AlertState {
+                        error,
+                        last_alert: now,
+                        since,
+                    }),
+                )
+            })
+        }
+    }
+}
+
+/// Alerts about a failed run unless the same failure was already alerted, or sends a recovery
+/// message if the run succeeded after a failure
+pub fn push_alerts(config: &Config, result: &Result<()>) -> Result<()> {
+    let Some(alerts_config) = config
+        .notifications
+        .as_ref()
+        .and_then(|notification_config| notification_config.alerts.as_ref())
+    else {
+        return Ok(());
+    };
+
+    let now = Local::now();
+    let error = result.as_ref().err().map(|e| {
+        e.chain()
+            .map(|cause| cause.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    let Some((alert, state)) =
+        next_alert(load_alert_state()?, error, alerts_config.repeat_hours, now)
+    else {
+        return Ok(());
+    };
+
+    let kind = if alert.recovered {
+        "recovery"
+    } else {
+        "failure"
+    };
+    println!(
+        "[{}] Sending {kind} alert.",
+        now.format("%Y-%m-%dT%H:%M:%S")
+    );
+    push_alert_notification(config, &alert)?;
+
+    let data =
+        serde_json::to_string_pretty(&state).with_context(|| "Failed to serialize alert state!")?;
+    save_to_cache(&data, ALERT_STATE_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 1, hour, 0, 0).unwrap()
+    }
+
+    fn failing(error: &str, since: u32, last_alert: u32) -> Option<AlertState> {
+        Some(AlertState {
+            error: error.to_string(),
+            last_alert: at(last_alert),
+            since: at(since),
+        })
+    }
+
+    #[test]
+    fn first_failure_is_alerted() {
+        let (alert, state) = next_alert(None, Some("timeout".to_string()), None, at(8)).unwrap();
+
+        assert!(!alert.recovered);
+        assert_eq!(alert.since, at(8));
+        assert_eq!(state.unwrap().last_alert, at(8));
+    }
+
+    #[test]
+    fn repeated_failure_is_suppressed() {
+        let alert = next_alert(
+            failing("timeout", 8, 8),
+            Some("timeout".to_string()),
+            Some(6),
+            at(12),
+        );
+
+        assert!(alert.is_none());
+    }
+
+    #[test]
+    fn repeated_failure_is_alerted_again_after_repeat_hours() {
+        let (alert, state) = next_alert(
+            failing("timeout", 8, 8),
+            Some("timeout".to_string()),
+            Some(6),
+            at(14),
+        )
+        .unwrap();
+        let state = state.unwrap();
+
+        assert!(!alert.recovered);
+        assert_eq!(alert.since, at(8));
+        assert_eq!((state.since, state.last_alert), (at(8), at(14)));
+    }
+
+    #[test]
+    fn different_failure_is_alerted() {
+        let (alert, _) = next_alert(
+            failing("timeout", 8, 8),
+            Some("not found".to_string()),
+            None,
+            at(9),
+        )
+        .unwrap();
+
+        assert_eq!(alert.error, "not found");
+        assert_eq!(alert.since, at(8));
+    }
+
+    #[test]
+    fn success_after_failure_sends_recovery() {
+        let (alert, state) = next_alert(failing("timeout", 8, 10), None, Some(6), at(12)).unwrap();
+
+        assert!(alert.recovered);
+        assert_eq!(alert.error, "timeout");
+        assert_eq!(alert.since, at(8));
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn success_without_failure_sends_nothing() {
+        assert!(next_alert(None, None, Some(6), at(12)).is_none());
+    }
+}
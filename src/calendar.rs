@@ -1,32 +1,81 @@
 use crate::cache::{is_cached, load_from_cache, save_to_cache};
 use crate::calendar::PipelineResult::{New, Nothing, Updated};
-use crate::config::{CalendarConfig, Config, NotificationConfig};
-use crate::diff::{DiffReport, generate_diff_report, raw_ics_identical};
+use crate::config::{CalendarConfig, Config, NotificationConfig, NotificationWindowConfig};
+use crate::diff::{
+    CalendarDiff, DEFAULT_IGNORED_PROPERTIES, generate_calendar_diff, ics_equivalent,
+};
 
 use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
 use icalendar::{Calendar, CalendarComponent, Component};
 use reqwest::blocking::get;
-use std::collections::HashSet;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use url::Url;
 
+static TOP_DROPPED_SUMMARIES: usize = 5;
+
 enum AllowList {
     BlackList(HashSet<String>),
     WhiteList(HashSet<String>),
 }
 
 struct AppCalendar {
+    ignored_properties: Vec<String>,
     list: AllowList,
     name: String,
+    notification_window: Option<NotificationWindowConfig>,
+    uid_match_threshold: Option<f32>,
     url: Url,
 }
 
+/// Event removed by the filter and the rule that removed it
+#[derive(Serialize)]
+pub struct DroppedEvent {
+    pub rule: String,
+    pub start: Option<String>,
+    pub summary: String,
+    pub uid: Option<String>,
+}
+
+/// Events removed by the last filtering of a calendar, published next to the filtered calendar
+#[derive(Serialize)]
+pub struct DroppedReport {
+    pub calendar_name: String,
+    pub created: DateTime<Local>,
+    pub dropped: Vec<DroppedEvent>,
+}
+
+/// Number of events kept by the filter and the events it dropped
+struct FilterResult {
+    kept: usize,
+    report: DroppedReport,
+}
+
+/// Result of filtering a calendar for the first time
+pub struct ImportSummary {
+    pub calendar_name: String,
+    pub dropped: usize,
+    pub kept: usize,
+    /// Most frequently dropped summaries with their number of events
+    pub top_dropped: Vec<(String, usize)>,
+}
+
+/// Calendars updated by a run and the reports about them, `dropped_reports` covers all calendars
+pub struct PipelineOutput {
+    pub diffs: Vec<CalendarDiff>,
+    pub dropped_reports: Vec<DroppedReport>,
+    pub imports: Vec<ImportSummary>,
+    pub updated_names: Vec<String>,
+}
+
 enum PipelineResult {
-    New(String),
-    Nothing,
-    Updated(Option<(String, DiffReport)>),
+    New(String, ImportSummary, DroppedReport),
+    Nothing(DroppedReport),
+    Updated(String, Option<CalendarDiff>, DroppedReport),
 }
 
-fn build_filtered_calendar(calendar: &AppCalendar) -> Result<()> {
+fn build_filtered_calendar(calendar: &AppCalendar) -> Result<FilterResult> {
     let filename = format!("{}.ics", calendar.name);
     if !is_cached(&filename) {
         bail!("Calendar '{}' is not cached!", calendar.name)
@@ -39,25 +88,7 @@ fn build_filtered_calendar(calendar: &AppCalendar) -> Result<()> {
         Err(e) => bail!("Failed to parse calendar '{}'!\n{e}", calendar.name),
     };
 
-    let mut filtered_calendar = Calendar::new();
-
-    for component in &parsed_calendar.components {
-        if let CalendarComponent::Event(event) = component {
-            let summary = event.get_summary().unwrap();
-            match &calendar.list {
-                AllowList::BlackList(black_list) => {
-                    if !black_list.contains(summary) {
-                        filtered_calendar.push(event.clone());
-                    }
-                }
-                AllowList::WhiteList(white_list) => {
-                    if white_list.contains(summary) {
-                        filtered_calendar.push(event.clone());
-                    }
-                }
-            }
-        }
-    }
+    let (filtered_calendar, result) = filter_calendar(calendar, &parsed_calendar);
 
     save_to_cache(
         &filtered_calendar.to_string(),
@@ -65,28 +96,44 @@ fn build_filtered_calendar(calendar: &AppCalendar) -> Result<()> {
     )
     .with_context(|| "Failed to save filtered calendar to cache!")?;
 
-    Ok(())
+    Ok(result)
 }
 
 fn calendar_from_config(calendar_config: &CalendarConfig) -> Result<AppCalendar> {
-    let name = if calendar_config.name.is_none() {
-        get_calendar_name(&calendar_config.url)?
-    } else {
-        calendar_config.name.clone().unwrap()
+    let name = calendar_name(calendar_config)?;
+
+    if let Some(threshold) = calendar_config.uid_match_threshold
+        && !(0.0..=1.0).contains(&threshold)
+    {
+        bail!("Calendar '{name}' has an invalid UID match threshold! Must be between 0.0 and 1.0.")
+    }
+
+    let ignored_properties = match &calendar_config.ignored_properties {
+        Some(properties) => properties.clone(),
+        None => DEFAULT_IGNORED_PROPERTIES
+            .iter()
+            .map(|property| property.to_string())
+            .collect(),
     };
 
     if let Some(list_content) = calendar_config.whitelist.clone() {
         let list_content: HashSet<String> = list_content.into_iter().collect();
         Ok(AppCalendar {
+            ignored_properties,
             list: AllowList::WhiteList(list_content),
             name,
+            notification_window: calendar_config.notification_window.clone(),
+            uid_match_threshold: calendar_config.uid_match_threshold,
             url: calendar_config.url.clone(),
         })
     } else if let Some(list_content) = calendar_config.blacklist.clone() {
         let list_content: HashSet<String> = list_content.into_iter().collect();
         Ok(AppCalendar {
+            ignored_properties,
             list: AllowList::BlackList(list_content),
             name,
+            notification_window: calendar_config.notification_window.clone(),
+            uid_match_threshold: calendar_config.uid_match_threshold,
             url: calendar_config.url.clone(),
         })
     } else if calendar_config.blacklist.is_some() && calendar_config.whitelist.is_some() {
@@ -96,14 +143,65 @@ fn calendar_from_config(calendar_config: &CalendarConfig) -> Result<AppCalendar>
     }
 }
 
+/// Name of the calendar, derived from the last URL segment if not configured
+pub fn calendar_name(calendar_config: &CalendarConfig) -> Result<String> {
+    match &calendar_config.name {
+        Some(name) => Ok(name.clone()),
+        None => get_calendar_name(&calendar_config.url),
+    }
+}
+
 fn fetch_calendar(url: &Url) -> Result<String> {
-    let response =
-        get(url.clone()).with_context(|| format!("Failed to fetch calendar from '{url}'!"))?;
+    let response = get(url.clone())
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch calendar from '{url}'!"))?;
     response
         .text()
         .with_context(|| "Failed to decode response body!")
 }
 
+/// Splits the events of a calendar into the kept ones and the report of the dropped ones
+fn filter_calendar(calendar: &AppCalendar, parsed_calendar: &Calendar) -> (Calendar, FilterResult) {
+    let mut filtered_calendar = Calendar::new();
+    let mut result = FilterResult {
+        kept: 0,
+        report: DroppedReport {
+            calendar_name: calendar.name.clone(),
+            created: Local::now(),
+            dropped: Vec::new(),
+        },
+    };
+
+    for component in &parsed_calendar.components {
+        if let CalendarComponent::Event(event) = component {
+            let summary = event.get_summary().unwrap_or_default();
+            let rule = match &calendar.list {
+                AllowList::BlackList(black_list) => black_list
+                    .contains(summary)
+                    .then(|| format!("blacklist: \"{summary}\"")),
+                AllowList::WhiteList(white_list) => {
+                    (!white_list.contains(summary)).then(|| "not in whitelist".to_string())
+                }
+            };
+
+            match rule {
+                Some(rule) => result.report.dropped.push(DroppedEvent {
+                    rule,
+                    start: event.property_value("DTSTART").map(String::from),
+                    summary: summary.to_string(),
+                    uid: event.get_uid().map(String::from),
+                }),
+                None => {
+                    filtered_calendar.push(event.clone());
+                    result.kept += 1;
+                }
+            }
+        }
+    }
+
+    (filtered_calendar, result)
+}
+
 fn get_calendar_name(url: &Url) -> Result<String> {
     let mut segments = url
         .path_segments()
@@ -122,6 +220,28 @@ fn get_calendar_name(url: &Url) -> Result<String> {
     Ok(name.to_string())
 }
 
+/// Summary of the first filtering of a calendar
+fn import_summary(name: &str, result: &FilterResult) -> ImportSummary {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for event in &result.report.dropped {
+        *counts.entry(&event.summary).or_default() += 1;
+    }
+
+    let mut top_dropped: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(summary, count)| (summary.to_string(), count))
+        .collect();
+    top_dropped.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_dropped.truncate(TOP_DROPPED_SUMMARIES);
+
+    ImportSummary {
+        calendar_name: name.to_string(),
+        dropped: result.report.dropped.len(),
+        kept: result.kept,
+        top_dropped,
+    }
+}
+
 fn pipeline_for_calendar(
     calendar_config: &CalendarConfig,
     notification_config: &Option<NotificationConfig>,
@@ -132,18 +252,23 @@ fn pipeline_for_calendar(
 
     if !is_cached(&ics_filename) {
         save_to_cache(&raw_ics, &ics_filename)?;
-        build_filtered_calendar(&calendar)?;
-        return Ok(New(calendar.name));
+        let result = build_filtered_calendar(&calendar)?;
+        let summary = import_summary(&calendar.name, &result);
+        return Ok(New(calendar.name, summary, result.report));
     }
 
     let raw_ics_cached = load_from_cache(&ics_filename)?;
 
-    if raw_ics_identical(&raw_ics_cached, &raw_ics)? {
-        return Ok(Nothing);
+    if ics_equivalent(&raw_ics_cached, &raw_ics, &calendar.ignored_properties)
+        .with_context(|| format!("Failed to compare calendar '{}'!", calendar.name))?
+    {
+        // Filtered again to report the dropped events on every run
+        let report = build_filtered_calendar(&calendar)?.report;
+        return Ok(Nothing(report));
     }
 
     let old_filtered_cal_raw = load_from_cache(&format!("{}_filtered.ics", calendar.name))?;
-    build_filtered_calendar(&calendar)?;
+    let report = build_filtered_calendar(&calendar)?.report;
     save_to_cache(&raw_ics, &ics_filename)?;
     let new_filtered_cal_raw = load_from_cache(&format!("{}_filtered.ics", calendar.name))?;
 
@@ -159,31 +284,126 @@ fn pipeline_for_calendar(
     };
 
     match notification_config {
-        Some(cfg) => {
-            let report = generate_diff_report(&old_calendar, &new_calendar, cfg.format_as_table)?;
-            Ok(Updated(Some((calendar.name, report))))
+        Some(_) => {
+            let diff = generate_calendar_diff(
+                &calendar.name,
+                &old_calendar,
+                &new_calendar,
+                calendar.uid_match_threshold,
+                calendar.notification_window.as_ref(),
+            )?;
+            Ok(Updated(calendar.name, Some(diff), report))
         }
-        None => Ok(Updated(None)),
+        None => Ok(Updated(calendar.name, None, report)),
     }
 }
 
-pub fn run_pipeline(config: &Config) -> Result<(Vec<String>, Vec<DiffReport>)> {
+pub fn run_pipeline(config: &Config) -> Result<PipelineOutput> {
     let calendar_count = config.calendars.len();
-    let mut reports = Vec::with_capacity(calendar_count);
-    let mut updated_names = Vec::with_capacity(calendar_count);
+    let mut output = PipelineOutput {
+        diffs: Vec::with_capacity(calendar_count),
+        dropped_reports: Vec::new(),
+        imports: Vec::new(),
+        updated_names: Vec::with_capacity(calendar_count),
+    };
 
     for calendar in &config.calendars {
         match pipeline_for_calendar(calendar, &config.notifications)? {
-            New(result) => updated_names.push(result),
-            Nothing => (),
-            Updated(result) => {
-                if let Some((name, diff_report)) = result {
-                    updated_names.push(name);
-                    reports.push(diff_report);
+            New(name, summary, report) => {
+                output.updated_names.push(name);
+                output.imports.push(summary);
+                output.dropped_reports.push(report);
+            }
+            Nothing(report) => output.dropped_reports.push(report),
+            Updated(name, diff, report) => {
+                output.updated_names.push(name);
+                output.dropped_reports.push(report);
+                if let Some(diff) = diff {
+                    output.diffs.push(diff);
                 }
             }
         }
     }
 
-    Ok((updated_names, reports))
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_calendar(list: AllowList) -> AppCalendar {
+        AppCalendar {
+            ignored_properties: Vec::new(),
+            list,
+            name: "Work".to_string(),
+            notification_window: None,
+            uid_match_threshold: None,
+            url: Url::parse("https://example.org/work.ics").unwrap(),
+        }
+    }
+
+    fn calendar(events: &[&str]) -> Calendar {
+        let events: String = events
+            .iter()
+            .map(|event| format!("BEGIN:VEVENT\r\n{event}\r\nEND:VEVENT\r\n"))
+            .collect();
+        format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{events}END:VCALENDAR\r\n")
+            .parse()
+            .unwrap()
+    }
+
+    fn list(summaries: &[&str]) -> HashSet<String> {
+        summaries
+            .iter()
+            .map(|summary| summary.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn events_without_summary_are_filtered() {
+        let parsed = calendar(&[
+            "UID:a\r\nDTSTART:20250106T100000",
+            "UID:b\r\nSUMMARY:Lecture",
+        ]);
+
+        let (_, blacklisted) = filter_calendar(
+            &app_calendar(AllowList::BlackList(list(&["Lecture"]))),
+            &parsed,
+        );
+        let (_, whitelisted) = filter_calendar(
+            &app_calendar(AllowList::WhiteList(list(&["Lecture"]))),
+            &parsed,
+        );
+
+        assert_eq!(blacklisted.kept, 1);
+        assert_eq!(blacklisted.report.dropped[0].uid.as_deref(), Some("b"));
+        assert_eq!(whitelisted.kept, 1);
+        assert_eq!(whitelisted.report.dropped[0].uid.as_deref(), Some("a"));
+        assert_eq!(whitelisted.report.dropped[0].summary, "");
+    }
+
+    #[test]
+    fn import_summary_counts_the_most_dropped_summaries() {
+        let mut events = Vec::new();
+        for (summary, count) in [("A", 1), ("B", 3), ("C", 2), ("D", 2), ("E", 1), ("F", 4)] {
+            for i in 0..count {
+                events.push(format!("UID:{summary}{i}\r\nSUMMARY:{summary}"));
+            }
+        }
+        events.push("UID:kept\r\nSUMMARY:Kept".to_string());
+        let events: Vec<&str> = events.iter().map(String::as_str).collect();
+        let app = app_calendar(AllowList::WhiteList(list(&["Kept"])));
+
+        let (_, result) = filter_calendar(&app, &calendar(&events));
+        let summary = import_summary("Work", &result);
+
+        assert_eq!(summary.kept, 1);
+        assert_eq!(summary.dropped, 13);
+        assert_eq!(
+            summary.top_dropped,
+            [("F", 4), ("B", 3), ("C", 2), ("D", 2), ("A", 1)]
+                .map(|(summary, count)| (summary.to_string(), count))
+        );
+    }
 }
@@ -1,5 +1,9 @@
 use anyhow::{Context, Result, bail};
-use serde::Deserialize;
+use chrono::{NaiveTime, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
 use std::{fs::read_to_string, path::Path};
 use url::Url;
 
@@ -10,28 +14,127 @@ pub struct Config {
     pub calendars: Vec<CalendarConfig>,
     pub git: Option<GitConfig>,
     pub notifications: Option<NotificationConfig>,
+    pub stale_rules: Option<StaleRulesConfig>,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Channel {
+    Discord,
+    Email,
+    Gotify,
+    Matrix,
+    Mattermost,
+    Ntfy,
+    Slack,
+    Telegram,
+    Webhook,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::Discord => "discord",
+            Channel::Email => "email",
+            Channel::Gotify => "gotify",
+            Channel::Matrix => "matrix",
+            Channel::Mattermost => "mattermost",
+            Channel::Ntfy => "ntfy",
+            Channel::Slack => "slack",
+            Channel::Telegram => "telegram",
+            Channel::Webhook => "webhook",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Alerts about failed runs, sent to the configured channels or to the overriding recipients
+#[derive(Clone, Deserialize)]
+pub struct AlertsConfig {
+    /// Repeats the alert of a persistent failure after this many hours, only alerts once if `None`
+    pub repeat_hours: Option<u32>,
+    #[serde(flatten)]
+    pub route: NotificationRoutingConfig,
+}
+
+/// Scheduled message listing the upcoming events of a filtered calendar
+#[derive(Clone, Deserialize)]
+pub struct AgendaConfig {
+    pub period: Option<AgendaPeriod>,
+    pub send_at: NaiveTime,
+    /// Timezone of `send_at` and the listed times, defaults to the local timezone
+    pub timezone: Option<Tz>,
+    /// Only send on this day of the week
+    pub weekday: Option<Weekday>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AgendaPeriod {
+    NextWeek,
+    Today,
+    #[default]
+    Tomorrow,
 }
 
 #[derive(Clone, Deserialize)]
 pub struct CalendarConfig {
+    pub agenda: Option<AgendaConfig>,
     pub blacklist: Option<Vec<String>>,
+    pub ignored_properties: Option<Vec<String>>,
     pub name: Option<String>,
+    pub notification_window: Option<NotificationWindowConfig>,
+    pub notifications: Option<NotificationRoutingConfig>,
+    pub reminders: Option<RemindersConfig>,
+    pub uid_match_threshold: Option<f32>,
     pub url: Url,
     pub whitelist: Option<Vec<String>>,
 }
 
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DigestMode {
+    Calendar,
+    #[default]
+    Disabled,
+    Run,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct EmailConfig {
-    pub smtp_server: String,
-    pub username: String,
-    pub password: String,
+    pub bcc: Option<Vec<String>>,
+    pub cc: Option<Vec<String>>,
+    pub digest: Option<DigestMode>,
+    pub format: Option<MessageFormat>,
+    /// Sender address, may include a display name. Defaults to the username
+    pub from: Option<String>,
+    /// Attach the changed events as iCalendar invitations
+    pub invitations: Option<InvitationMethod>,
+    pub password: Option<String>,
+    /// Defaults to the port of the TLS mode (25, 587 or 465) or the one included in `smtp_server`
+    pub port: Option<u16>,
     pub recipients: Vec<String>,
+    pub reply_to: Option<String>,
+    pub smtp_server: String,
+    pub tls: Option<SmtpTls>,
+    /// Authentication is skipped without username, e.g. for internal relays
+    pub username: Option<String>,
+    #[serde(flatten)]
+    pub templates: TemplateConfig,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
 }
 
 #[derive(Clone, Deserialize)]
 pub struct GotifyConfig {
+    pub format: Option<MessageFormat>,
+    pub priority: Option<u8>,
     pub server: String,
     pub token: String,
+    #[serde(flatten)]
+    pub templates: TemplateConfig,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
 }
 
 #[derive(Clone, Deserialize)]
@@ -54,11 +157,180 @@ pub struct GitSignatureConfig {
     pub username: String,
 }
 
+/// Slack, Discord and Mattermost compatible incoming webhook
+#[derive(Clone, Deserialize)]
+pub struct IncomingWebhookConfig {
+    pub digest: Option<DigestMode>,
+    pub url: Url,
+    #[serde(flatten)]
+    pub templates: TemplateConfig,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum InvitationMethod {
+    Publish,
+    Request,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MatrixConfig {
+    pub access_token: String,
+    pub digest: Option<DigestMode>,
+    pub homeserver: Url,
+    pub room_ids: Vec<String>,
+    #[serde(flatten)]
+    pub templates: TemplateConfig,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MessageFormat {
+    AsciiTable,
+    Html,
+    Markdown,
+    #[default]
+    Plain,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct NotificationWindowConfig {
+    pub days_ahead: Option<u32>,
+    #[serde(default)]
+    pub skip_past: bool,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct NotificationConfig {
+    pub alerts: Option<AlertsConfig>,
+    pub discord: Option<IncomingWebhookConfig>,
     pub email: Option<EmailConfig>,
     pub gotify: Option<GotifyConfig>,
-    pub format_as_table: bool,
+    pub matrix: Option<MatrixConfig>,
+    pub mattermost: Option<IncomingWebhookConfig>,
+    pub ntfy: Option<NtfyConfig>,
+    pub outbox: Option<OutboxConfig>,
+    pub slack: Option<IncomingWebhookConfig>,
+    pub telegram: Option<TelegramConfig>,
+    pub webhook: Option<WebhookConfig>,
+    pub format: Option<MessageFormat>,
+    /// Deprecated, use `format = "ascii-table"` instead
+    pub format_as_table: Option<bool>,
+    /// Also notify which events were removed from updated calendars by the filter
+    pub include_dropped: Option<bool>,
+}
+
+/// Restricts which channels receive the changes of a calendar and to whom they are sent
+#[derive(Clone, Deserialize)]
+pub struct NotificationRoutingConfig {
+    /// Channels receiving the changes, all configured channels if `None`
+    pub channels: Option<Vec<Channel>>,
+    /// Overrides the telegram chat ids
+    pub chat_ids: Option<Vec<String>>,
+    /// Overrides the email recipients
+    pub recipients: Option<Vec<String>>,
+    /// Overrides the matrix room ids
+    pub room_ids: Option<Vec<String>>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct NtfyConfig {
+    pub click_url: Option<Url>,
+    pub digest: Option<DigestMode>,
+    pub format: Option<MessageFormat>,
+    pub password: Option<String>,
+    pub priority: Option<u8>,
+    pub server: Url,
+    pub tags: Option<Vec<String>>,
+    pub token: Option<String>,
+    pub topic: String,
+    pub username: Option<String>,
+    #[serde(flatten)]
+    pub templates: TemplateConfig,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+}
+
+/// Retry behaviour of notifications that could not be delivered
+#[derive(Clone, Deserialize)]
+pub struct OutboxConfig {
+    pub max_age_hours: Option<u32>,
+    pub retry_interval_minutes: Option<u32>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RemindersConfig {
+    /// Also remind of all-day events, relative to their start at midnight
+    #[serde(default)]
+    pub all_day: bool,
+    pub minutes_before: Vec<u32>,
+}
+
+/// Daily window in which no notifications are delivered, may span midnight
+#[derive(Clone, Deserialize)]
+pub struct QuietHoursConfig {
+    pub end: NaiveTime,
+    pub start: NaiveTime,
+    /// Defaults to the local timezone
+    pub timezone: Option<Tz>,
+}
+
+/// Holds back notifications of a channel, held back changes are delivered together
+#[derive(Clone, Default, Deserialize)]
+pub struct ScheduleConfig {
+    pub min_interval_hours: Option<u32>,
+    pub quiet_hours: Option<QuietHoursConfig>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpTls {
+    None,
+    Starttls,
+    #[default]
+    Tls,
+}
+
+/// When a black- or whitelist entry counts as stale, after 30 days without a match if neither is set
+#[derive(Clone, Deserialize)]
+pub struct StaleRulesConfig {
+    pub after_days: Option<u32>,
+    pub after_runs: Option<u32>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct TelegramConfig {
+    pub api_url: Option<Url>,
+    pub bot_token: String,
+    pub chat_ids: Vec<String>,
+    pub digest: Option<DigestMode>,
+    #[serde(flatten)]
+    pub templates: TemplateConfig,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
+}
+
+/// User supplied templates, rendered with a Jinja-like template engine
+#[derive(Clone, Default, Deserialize)]
+pub struct TemplateConfig {
+    pub body_template: Option<String>,
+    pub title_template: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub headers: Option<HashMap<String, String>>,
+    pub retries: Option<u32>,
+    pub secret: Option<String>,
+    pub signature_header: Option<String>,
+    pub template: Option<String>,
+    pub url: Url,
+    #[serde(flatten)]
+    pub schedule: ScheduleConfig,
 }
 
 pub fn load_config() -> Result<Config> {
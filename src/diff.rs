@@ -1,22 +1,42 @@
-use anyhow::{Context, Result};
-use icalendar::{Calendar, CalendarComponent, Component, Event, EventLike};
-use prettytable::{Table, row};
-use regex::Regex;
-use similar::{ChangeTag, TextDiff};
+use crate::config::{MessageFormat, NotificationWindowConfig};
+use crate::recurrence::occurs_between;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Duration, Local, TimeZone};
+use icalendar::parser::{self, read_calendar, unfold};
+use icalendar::{
+    Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime, Event, EventLike,
+};
+use prettytable::{Cell, Row, Table, row};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use std::cmp::PartialEq;
 use std::collections::{HashMap, HashSet};
 
-static TIMESTAMP_REGEX: &str = r"DTSTAMP:\d{8}T\d{6}Z";
+pub use stringify::{escape_html, escape_markdown};
 
-#[derive(Default)]
-struct CalendarDiff {
-    deletions: Vec<Event>,
-    insertions: Vec<Event>,
-    modifications: Vec<(Event, Event)>,
+pub static DEFAULT_IGNORED_PROPERTIES: [&str; 4] = ["DTSTAMP", "LAST-MODIFIED", "SEQUENCE", "X-*"];
+
+/// Serializable representation of a calendar diff, used for webhooks and templates
+#[derive(Clone, Serialize)]
+pub struct CalendarChanges {
+    pub deletions: Vec<EventData>,
+    pub insertions: Vec<EventData>,
+    pub modifications: Vec<EventChange>,
+    pub name: String,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(into = "StoredCalendarDiff", try_from = "StoredCalendarDiff")]
+pub struct CalendarDiff {
+    pub calendar_name: String,
+    pub deletions: Vec<Event>,
+    pub insertions: Vec<Event>,
+    pub modifications: Vec<(Event, Event)>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum ChangeType {
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+pub enum ChangeType {
     Deletion,
     Insertion,
     Modification,
@@ -24,11 +44,31 @@ enum ChangeType {
 
 #[derive(Debug, Default)]
 pub struct DiffReport {
+    pub calendar_name: String,
     pub deletions: Vec<String>,
     pub insertions: Vec<String>,
     pub modifications: Vec<String>,
 }
 
+/// Serializable representation of an event, used for machine readable notifications
+#[derive(Clone, Serialize)]
+pub struct EventData {
+    pub description: Option<String>,
+    pub end: Option<String>,
+    pub location: Option<String>,
+    pub priority: Option<u32>,
+    pub start: Option<String>,
+    pub summary: Option<String>,
+    pub uid: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct EventChange {
+    pub changes: Vec<FieldChange>,
+    pub new: EventData,
+    pub old: EventData,
+}
+
 struct EventDiff<'a> {
     field_diff: Vec<(EventField, ChangeType)>,
     new: &'a Event,
@@ -45,9 +85,85 @@ enum EventField {
     Summary,
 }
 
+/// Calendar diff with the events as iCalendar text, used to persist held back changes
+#[derive(Deserialize, Serialize)]
+struct StoredCalendarDiff {
+    calendar_name: String,
+    deletions: Vec<String>,
+    insertions: Vec<String>,
+    modifications: Vec<(String, String)>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub new: Option<String>,
+    pub old: Option<String>,
+}
+
+impl From<&Event> for EventData {
+    fn from(event: &Event) -> Self {
+        let date_to_string = |date: DatePerhapsTime| match date {
+            DatePerhapsTime::Date(date) => Some(date.format("%Y-%m-%d").to_string()),
+            date_time => date_to_local(&date_time).map(|date_time| date_time.to_rfc3339()),
+        };
+
+        EventData {
+            description: event.get_description().map(String::from),
+            end: event.get_end().and_then(date_to_string),
+            location: event.get_location().map(String::from),
+            priority: event.get_priority(),
+            start: event.get_start().and_then(date_to_string),
+            summary: event.get_summary().map(String::from),
+            uid: event.get_uid().map(String::from),
+        }
+    }
+}
+
+impl From<CalendarDiff> for StoredCalendarDiff {
+    fn from(diff: CalendarDiff) -> Self {
+        StoredCalendarDiff {
+            calendar_name: diff.calendar_name,
+            deletions: diff.deletions.iter().map(Component::to_string).collect(),
+            insertions: diff.insertions.iter().map(Component::to_string).collect(),
+            modifications: diff
+                .modifications
+                .iter()
+                .map(|(old, new)| (old.to_string(), new.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<StoredCalendarDiff> for CalendarDiff {
+    type Error = anyhow::Error;
+
+    fn try_from(diff: StoredCalendarDiff) -> Result<Self> {
+        let mut modifications = Vec::with_capacity(diff.modifications.len());
+        for (old, new) in &diff.modifications {
+            modifications.push((parse_event(old)?, parse_event(new)?));
+        }
+
+        Ok(CalendarDiff {
+            calendar_name: diff.calendar_name,
+            deletions: diff
+                .deletions
+                .iter()
+                .map(|event| parse_event(event))
+                .collect::<Result<_>>()?,
+            insertions: diff
+                .insertions
+                .iter()
+                .map(|event| parse_event(event))
+                .collect::<Result<_>>()?,
+            modifications,
+        })
+    }
+}
+
 impl<'a> EventDiff<'a> {
-    fn to_string_table(&self) -> Result<String> {
-        let comparison_result = stringify::event_diff_to_comparison_rows(self)?;
+    fn comparison_rows(&self, wrap_description: bool) -> Result<Vec<[String; 3]>> {
+        let comparison_result = stringify::event_diff_to_comparison_rows(self, wrap_description)?;
         let mut event_fields = comparison_result.0;
         let evt_field_mod_tracker = comparison_result.1;
 
@@ -55,11 +171,120 @@ impl<'a> EventDiff<'a> {
             self.old,
             &mut event_fields,
             evt_field_mod_tracker,
+            wrap_description,
         )?;
 
+        Ok(event_fields)
+    }
+
+    /// Renders modified free text fields (summary, location, description) as inline word diffs,
+    /// indexed like the comparison rows.
+    fn inline_diffs(&self, format: MessageFormat) -> Result<[Option<String>; 6]> {
+        let mut inline_diffs = [const { None }; 6];
+
+        for (field, change_type) in &self.field_diff {
+            let is_text_field = matches!(
+                field,
+                EventField::Description | EventField::Location | EventField::Summary
+            );
+            if !is_text_field || *change_type != ChangeType::Modification {
+                continue;
+            }
+
+            let old_value = stringify::extract_evt_field_as_str(field, self.old, false)?;
+            let new_value = stringify::extract_evt_field_as_str(field, self.new, false)?;
+            inline_diffs[stringify::row_index(field)] =
+                Some(stringify::inline_diff(&old_value, &new_value, format));
+        }
+
+        Ok(inline_diffs)
+    }
+
+    fn to_html(&self) -> Result<String> {
+        let event_fields = self.comparison_rows(false)?;
+        let inline_diffs = self.inline_diffs(MessageFormat::Html)?;
+        let mut result = String::from("<table>\n");
+
+        for (idx, field) in event_fields.iter().enumerate() {
+            let value = if let Some(inline_diff) = &inline_diffs[idx] {
+                inline_diff.clone()
+            } else if field[1] == field[2] {
+                stringify::escape_html(&field[1])
+            } else {
+                format!(
+                    "<del>{}</del> <ins>{}</ins>",
+                    stringify::escape_html(&field[1]),
+                    stringify::escape_html(&field[2])
+                )
+            };
+
+            let row = if idx == 0 {
+                format!("<tr><th colspan=\"2\">{value}</th></tr>\n")
+            } else if field[1] == field[2] {
+                format!("<tr><td>{}</td><td>{value}</td></tr>\n", field[0])
+            } else {
+                format!(
+                    "<tr style=\"{}\"><td><b>{}</b></td><td>{value}</td></tr>\n",
+                    stringify::HTML_HIGHLIGHT_STYLE,
+                    field[0]
+                )
+            };
+
+            result.push_str(&row);
+        }
+
+        result.push_str("</table>\n");
+        Ok(result)
+    }
+
+    fn to_markdown(&self) -> Result<String> {
+        let event_fields = self.comparison_rows(false)?;
+        let inline_diffs = self.inline_diffs(MessageFormat::Markdown)?;
+        let mut result = String::new();
+
+        for (idx, field) in event_fields.iter().enumerate() {
+            let value = if let Some(inline_diff) = &inline_diffs[idx] {
+                inline_diff.clone()
+            } else if field[1] == field[2] {
+                stringify::escape_markdown(field[1].trim_end())
+            } else {
+                format!(
+                    "~~{}~~ → **{}**",
+                    stringify::escape_markdown(field[1].trim_end()),
+                    stringify::escape_markdown(field[2].trim_end())
+                )
+            };
+
+            let row = if idx == 0 && inline_diffs[idx].is_some() {
+                // The inline diff already uses bold text for insertions
+                format!("{value}\n\n")
+            } else if idx == 0 {
+                format!("**{value}**\n\n")
+            } else {
+                format!("- **{}:** {value}\n", field[0])
+            };
+
+            result.push_str(&row);
+        }
+
+        Ok(result)
+    }
+
+    fn to_string_table(&self) -> Result<String> {
+        let event_fields = self.comparison_rows(true)?;
+        let inline_diffs = self.inline_diffs(MessageFormat::AsciiTable)?;
         let mut table = Table::new();
 
-        for field in event_fields {
+        for (idx, field) in event_fields.iter().enumerate() {
+            if let Some(inline_diff) = &inline_diffs[idx] {
+                let value = stringify::trim_description(inline_diff);
+                table.add_row(Row::new(vec![
+                    Cell::new(&field[0]),
+                    Cell::new(&value).with_hspan(2),
+                ]));
+                continue;
+            }
+
             table.add_row(row![
                 field[0].as_str(),
                 field[1].as_str(),
@@ -71,20 +296,14 @@ impl<'a> EventDiff<'a> {
     }
 
     fn to_string(&self) -> Result<String> {
-        let comparison_result = stringify::event_diff_to_comparison_rows(self)?;
-        let mut event_fields = comparison_result.0;
-        let evt_field_mod_tracker = comparison_result.1;
-
-        stringify::insert_unmodified_event_fields(
-            self.old,
-            &mut event_fields,
-            evt_field_mod_tracker,
-        )?;
-
+        let event_fields = self.comparison_rows(true)?;
+        let inline_diffs = self.inline_diffs(MessageFormat::Plain)?;
         let mut result = String::new();
 
-        for field in event_fields {
-            let row = if field[1] == field[2] {
+        for (idx, field) in event_fields.iter().enumerate() {
+            let row = if let Some(inline_diff) = &inline_diffs[idx] {
+                format!("{}: {inline_diff}\n", field[0])
+            } else if field[1] == field[2] {
                 format!("{}: {}\n", field[0], field[1])
             } else {
                 format!("{}: {} -> {}\n", field[0], field[1], field[2])
@@ -97,7 +316,125 @@ impl<'a> EventDiff<'a> {
     }
 }
 
-fn diff_calendars(old: &Calendar, new: &Calendar) -> Result<CalendarDiff> {
+pub fn calendar_changes(diff: &CalendarDiff) -> Result<CalendarChanges> {
+    let mut modifications = Vec::with_capacity(diff.modifications.len());
+    for (old, new) in &diff.modifications {
+        modifications.push(EventChange {
+            changes: field_changes(old, new)?,
+            new: EventData::from(new),
+            old: EventData::from(old),
+        });
+    }
+
+    Ok(CalendarChanges {
+        deletions: diff.deletions.iter().map(EventData::from).collect(),
+        insertions: diff.insertions.iter().map(EventData::from).collect(),
+        modifications,
+        name: diff.calendar_name.clone(),
+    })
+}
+
+fn canonicalize_component(component: &parser::Component, ignored_properties: &[String]) -> String {
+    let mut lines = canonicalize_properties(&component.properties, ignored_properties);
+
+    let mut children: Vec<String> = component
+        .components
+        .iter()
+        .map(|child| canonicalize_component(child, ignored_properties))
+        .collect();
+    children.sort();
+    lines.append(&mut children);
+
+    let name = component.name.as_str().to_uppercase();
+    format!("BEGIN:{name}\n{}\nEND:{name}", lines.join("\n"))
+}
+
+fn canonicalize_ics(raw: &str, ignored_properties: &[String]) -> Result<Vec<String>> {
+    let unfolded = unfold(raw);
+
+    // Not using with_context() because "the trait bound `std::string::String: StdError` is not satisfied"
+    let calendar = match read_calendar(&unfolded) {
+        Ok(data) => data,
+        Err(e) => bail!("Failed to parse calendar!\n{e}"),
+    };
+
+    let mut lines = canonicalize_properties(&calendar.properties, ignored_properties);
+
+    let mut components: Vec<String> = calendar
+        .components
+        .iter()
+        .map(|component| canonicalize_component(component, ignored_properties))
+        .collect();
+    components.sort();
+    lines.append(&mut components);
+
+    Ok(lines)
+}
+
+fn canonicalize_properties(
+    properties: &[parser::Property],
+    ignored_properties: &[String],
+) -> Vec<String> {
+    let mut lines = Vec::with_capacity(properties.len());
+
+    for property in properties {
+        let name = property.name.as_str().to_uppercase();
+        if property_ignored(&name, ignored_properties) {
+            continue;
+        }
+
+        let mut params: Vec<String> = property
+            .params
+            .iter()
+            .map(|param| match &param.val {
+                Some(val) => format!("{}={}", param.key.as_str().to_uppercase(), val.as_str()),
+                None => param.key.as_str().to_uppercase(),
+            })
+            .collect();
+        params.sort();
+
+        let mut line = name;
+        for param in params {
+            line.push(';');
+            line.push_str(&param);
+        }
+        line.push(':');
+        line.push_str(property.val.as_str());
+
+        lines.push(line);
+    }
+
+    lines.sort();
+    lines
+}
+
+/// Converts an event date to local time. Dates without time are treated as starting at midnight,
+/// floating times and unknown timezones are interpreted as local time.
+pub fn date_to_local(date: &DatePerhapsTime) -> Option<DateTime<Local>> {
+    let naive = match date {
+        DatePerhapsTime::Date(date) => date.and_hms_opt(0, 0, 0)?,
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(naive)) => *naive,
+        DatePerhapsTime::DateTime(
+            date_time @ CalendarDateTime::WithTimezone {
+                date_time: naive, ..
+            },
+        ) => match date_time.try_into_utc() {
+            Some(utc) => return Some(utc.with_timezone(&Local)),
+            None => *naive,
+        },
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(utc)) => {
+            return Some(utc.with_timezone(&Local));
+        }
+    };
+
+    Local.from_local_datetime(&naive).earliest()
+}
+
+fn diff_calendars(
+    old: &Calendar,
+    new: &Calendar,
+    uid_match_threshold: Option<f32>,
+) -> Result<CalendarDiff> {
     let (mut old_uids, old_events) = map_events(old)?;
     let (mut new_uids, new_events) = map_events(new)?;
     old_uids.append(&mut new_uids);
@@ -125,6 +462,10 @@ fn diff_calendars(old: &Calendar, new: &Calendar) -> Result<CalendarDiff> {
         }
     }
 
+    if let Some(threshold) = uid_match_threshold {
+        match_regenerated_uids(&mut calendar_diff, threshold);
+    }
+
     Ok(calendar_diff)
 }
 
@@ -222,7 +563,60 @@ fn diff_events<'a>(old: &'a Event, new: &'a Event) -> EventDiff<'a> {
     }
 }
 
-fn events_identical(event1: &Event, event2: &Event) -> bool {
+/// Checks whether an event falls into the notification window. Events without dates are
+/// always considered to be inside the window, recurring events if any instance is inside it.
+fn event_in_window(
+    event: &Event,
+    now: DateTime<Local>,
+    horizon: Option<DateTime<Local>>,
+    skip_past: bool,
+) -> bool {
+    let start = event.get_start().as_ref().and_then(date_to_local);
+    let end = event.get_end().as_ref().and_then(date_to_local).or(start);
+
+    // The master dates of a recurring event only describe its first instance
+    if event.property_value("RRULE").is_some() || event.multi_properties().contains_key("RDATE") {
+        let from = if skip_past { now } else { start.unwrap_or(now) };
+        return occurs_between(event, from, horizon);
+    }
+
+    if skip_past
+        && let Some(end) = end
+        && end < now
+    {
+        return false;
+    }
+
+    if let Some(horizon) = horizon
+        && let Some(start) = start
+        && start > horizon
+    {
+        return false;
+    }
+
+    true
+}
+
+/// Scores how likely two events are the same event, based on summary, start, end and location.
+/// Returns a value between 0.0 (completely different) and 1.0 (identical).
+fn event_similarity(event1: &Event, event2: &Event) -> f32 {
+    let summary = text_similarity(event1.get_summary(), event2.get_summary());
+    let date_start = if event1.get_start() == event2.get_start() {
+        1.0
+    } else {
+        0.0
+    };
+    let date_end = if event1.get_end() == event2.get_end() {
+        1.0
+    } else {
+        0.0
+    };
+    let location = text_similarity(event1.get_location(), event2.get_location());
+
+    (summary + date_start + date_end + location) / 4.0
+}
+
+pub fn events_identical(event1: &Event, event2: &Event) -> bool {
     let description = event1.get_description() == event2.get_description();
     let date_end = event1.get_end() == event2.get_end();
     let date_start = event1.get_start() == event2.get_start();
@@ -233,37 +627,89 @@ fn events_identical(event1: &Event, event2: &Event) -> bool {
     description && date_end && date_start && location && priority && summary
 }
 
-pub fn generate_diff_report(old: &Calendar, new: &Calendar, as_table: bool) -> Result<DiffReport> {
-    let mut report = DiffReport::default();
-    let diff = diff_calendars(old, new)?;
+/// Lists the modified fields of an event with their old and new values, `None` if the field
+/// was added or removed.
+pub fn field_changes(old: &Event, new: &Event) -> Result<Vec<FieldChange>> {
+    let event_diff = diff_events(old, new);
+    let mut changes = Vec::with_capacity(event_diff.field_diff.len());
 
-    for deletion in diff.deletions {
-        let deletion_str = if as_table {
-            stringify::event_to_string_table(&deletion)?
-        } else {
-            stringify::event_to_string(&deletion)?
+    for (field, change_type) in &event_diff.field_diff {
+        let old_value = match change_type {
+            ChangeType::Insertion => None,
+            _ => Some(stringify::extract_evt_field_as_str(field, old, false)?),
+        };
+        let new_value = match change_type {
+            ChangeType::Deletion => None,
+            _ => Some(stringify::extract_evt_field_as_str(field, new, false)?),
         };
 
-        report.deletions.push(deletion_str);
+        changes.push(FieldChange {
+            field: stringify::field_name(field),
+            new: new_value,
+            old: old_value,
+        });
     }
 
-    for insertion in diff.insertions {
-        let insertion_str = if as_table {
-            stringify::event_to_string_table(&insertion)?
-        } else {
-            stringify::event_to_string(&insertion)?
-        };
+    Ok(changes)
+}
+
+/// Drops all changes to events outside the notification window. A modification is kept
+/// if either the old or the new version of the event is inside the window.
+fn filter_by_window(calendar_diff: &mut CalendarDiff, window: &NotificationWindowConfig) {
+    let now = Local::now();
+    let horizon = window
+        .days_ahead
+        .map(|days| now + Duration::days(days.into()));
+    let in_window = |event: &Event| event_in_window(event, now, horizon, window.skip_past);
+
+    calendar_diff.deletions.retain(in_window);
+    calendar_diff.insertions.retain(in_window);
+    calendar_diff
+        .modifications
+        .retain(|(old, new)| in_window(old) || in_window(new));
+}
+
+pub fn generate_calendar_diff(
+    calendar_name: &str,
+    old: &Calendar,
+    new: &Calendar,
+    uid_match_threshold: Option<f32>,
+    window: Option<&NotificationWindowConfig>,
+) -> Result<CalendarDiff> {
+    let mut diff = diff_calendars(old, new, uid_match_threshold)?;
+    diff.calendar_name = calendar_name.to_string();
+
+    if let Some(window) = window {
+        filter_by_window(&mut diff, window);
+    }
 
+    Ok(diff)
+}
+
+pub fn generate_diff_report(diff: &CalendarDiff, format: MessageFormat) -> Result<DiffReport> {
+    let mut report = DiffReport {
+        calendar_name: diff.calendar_name.clone(),
+        ..Default::default()
+    };
+
+    for deletion in &diff.deletions {
+        let deletion_str = stringify::event_to_format(deletion, format)?;
+        report.deletions.push(deletion_str);
+    }
+
+    for insertion in &diff.insertions {
+        let insertion_str = stringify::event_to_format(insertion, format)?;
         report.insertions.push(insertion_str);
     }
 
-    for modifications in diff.modifications {
+    for modifications in &diff.modifications {
         let event_diff = diff_events(&modifications.0, &modifications.1);
 
-        let mod_str = if as_table {
-            event_diff.to_string_table()?
-        } else {
-            event_diff.to_string()?
+        let mod_str = match format {
+            MessageFormat::AsciiTable => event_diff.to_string_table()?,
+            MessageFormat::Html => event_diff.to_html()?,
+            MessageFormat::Markdown => event_diff.to_markdown()?,
+            MessageFormat::Plain => event_diff.to_string()?,
         };
 
         report.modifications.push(mod_str);
@@ -272,6 +718,15 @@ pub fn generate_diff_report(old: &Calendar, new: &Calendar, as_table: bool) -> R
     Ok(report)
 }
 
+/// Compares two raw ICS files after normalizing line folding, property and component order,
+/// ignoring all properties matched by `ignored_properties`.
+pub fn ics_equivalent(old: &str, new: &str, ignored_properties: &[String]) -> Result<bool> {
+    let old = canonicalize_ics(old, ignored_properties)?;
+    let new = canonicalize_ics(new, ignored_properties)?;
+
+    Ok(old == new)
+}
+
 fn map_events(calendar: &Calendar) -> Result<(Vec<&str>, HashMap<&str, Event>)> {
     let mut event_uids = Vec::new();
     let mut events = HashMap::new();
@@ -287,38 +742,122 @@ fn map_events(calendar: &Calendar) -> Result<(Vec<&str>, HashMap<&str, Event>)>
     Ok((event_uids, events))
 }
 
-pub fn raw_ics_identical(old: &str, new: &str) -> Result<bool> {
-    let regex =
-        Regex::new(TIMESTAMP_REGEX).with_context(|| "Failed to compile regular expression!")?;
+/// Pairs deleted and inserted events whose similarity reaches `threshold`, because some
+/// providers regenerate event UIDs on every export. Paired events are treated as the same
+/// event and reported as modified, or dropped from the diff if they are identical.
+fn match_regenerated_uids(calendar_diff: &mut CalendarDiff, threshold: f32) {
+    let mut candidates = Vec::new();
+
+    for (deletion_idx, deletion) in calendar_diff.deletions.iter().enumerate() {
+        for (insertion_idx, insertion) in calendar_diff.insertions.iter().enumerate() {
+            let similarity = event_similarity(deletion, insertion);
+            if similarity >= threshold {
+                candidates.push((similarity, deletion_idx, insertion_idx));
+            }
+        }
+    }
+
+    // Best matches first, so every event is paired with its most similar counterpart
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut matched_deletions = vec![false; calendar_diff.deletions.len()];
+    let mut matched_insertions = vec![false; calendar_diff.insertions.len()];
 
-    for diff in TextDiff::from_lines(old, new).iter_all_changes() {
-        if diff.tag() == ChangeTag::Equal {
+    for (_, deletion_idx, insertion_idx) in candidates {
+        if matched_deletions[deletion_idx] || matched_insertions[insertion_idx] {
             continue;
         }
+        matched_deletions[deletion_idx] = true;
+        matched_insertions[insertion_idx] = true;
 
-        if !regex.is_match(&diff.to_string()) {
-            // Ignore the 'DTSTAMP' field
-            return Ok(false);
+        let old = &calendar_diff.deletions[deletion_idx];
+        let new = &calendar_diff.insertions[insertion_idx];
+        if !events_identical(old, new) {
+            calendar_diff.modifications.push((old.clone(), new.clone()));
         }
     }
 
-    Ok(true)
+    let mut matched_deletions = matched_deletions.into_iter();
+    calendar_diff
+        .deletions
+        .retain(|_| !matched_deletions.next().unwrap());
+    let mut matched_insertions = matched_insertions.into_iter();
+    calendar_diff
+        .insertions
+        .retain(|_| !matched_insertions.next().unwrap());
+}
+
+fn parse_event(ics: &str) -> Result<Event> {
+    let calendar: Calendar = format!("BEGIN:VCALENDAR\r\n{ics}END:VCALENDAR\r\n")
+        .parse()
+        .map_err(|e: String| anyhow!(e))
+        .with_context(|| "Failed to parse stored event")?;
+
+    calendar
+        .components
+        .into_iter()
+        .find_map(|component| match component {
+            CalendarComponent::Event(event) => Some(event),
+            _ => None,
+        })
+        .context("Stored event is missing")
+}
+
+fn property_ignored(name: &str, ignored_properties: &[String]) -> bool {
+    ignored_properties.iter().any(|pattern| {
+        let pattern = pattern.to_uppercase();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        }
+    })
+}
+
+fn text_similarity(old: Option<&str>, new: Option<&str>) -> f32 {
+    match (old, new) {
+        (Some(old), Some(new)) => TextDiff::from_chars(old, new).ratio(),
+        (None, None) => 1.0,
+        _ => 0.0,
+    }
 }
 
 mod stringify {
     use super::{ChangeType, Event, EventDiff, EventField};
+    use crate::config::MessageFormat;
     use anyhow::Result;
-    use chrono::NaiveDateTime;
-    use icalendar::{Component, DatePerhapsTime, EventLike};
+    use chrono::Local;
+    use icalendar::{CalendarDateTime, Component, DatePerhapsTime, EventLike};
     use prettytable::{Table, row};
+    use similar::{ChangeTag, TextDiff};
 
+    static DELETED_LINE_BREAK: &str = "¶";
+    static DIFF_CONTEXT_LINES: usize = 1;
+    pub static HTML_HIGHLIGHT_STYLE: &str = "background-color:#fff3cd";
     static MAX_CELL_WIDTH: usize = 40;
     static EVENT_FIELD_STR: [&str; 6] = ["", "start", "end", "location", "priority", "description"];
+    static EVENT_STR: [&str; 7] = [
+        "",
+        "date",
+        "start",
+        "end",
+        "location",
+        "priority",
+        "description",
+    ];
 
     fn date_to_str(date: &DatePerhapsTime) -> Result<(String, String)> {
-        let raw = date.to_property("START");
-        let raw = raw.value();
-        let date_time = NaiveDateTime::parse_from_str(raw, "%Y%m%dT%H%M%S")?;
+        let date_time = match date {
+            DatePerhapsTime::Date(date) => {
+                return Ok((date.format("%d.%m.%Y").to_string(), "all day".to_string()));
+            }
+            DatePerhapsTime::DateTime(CalendarDateTime::Floating(naive)) => *naive,
+            DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, .. }) => {
+                *date_time
+            }
+            DatePerhapsTime::DateTime(CalendarDateTime::Utc(utc)) => {
+                utc.with_timezone(&Local).naive_local()
+            }
+        };
 
         let date = date_time.format("%d.%m.%Y").to_string();
         let time = date_time.format("%H:%M").to_string();
@@ -326,21 +865,51 @@ mod stringify {
         Ok((date, time))
     }
 
+    pub fn escape_html(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+
+        for c in value.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                '\n' => escaped.push_str("<br>"),
+                _ => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+
+    pub fn escape_markdown(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+
+        for c in value.chars() {
+            match c {
+                '\\' | '`' | '*' | '_' | '~' | '[' | ']' | '<' | '>' | '#' | '|' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                // Hard line break that keeps the text inside the surrounding list item
+                '\n' => escaped.push_str("  \n  "),
+                _ => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+
     pub fn event_diff_to_comparison_rows(
         event_diff: &EventDiff,
+        wrap_description: bool,
     ) -> Result<(Vec<[String; 3]>, [bool; 6])> {
         let mut rows = vec![[const { String::new() }; 3]; 6];
         let mut evt_field_mod_tracker = [false; 6];
 
         for diff in &event_diff.field_diff {
-            let idx = match diff.0 {
-                EventField::DateEnd => 2,
-                EventField::DateStart => 1,
-                EventField::Description => 5,
-                EventField::Location => 3,
-                EventField::Priority => 4,
-                EventField::Summary => 0,
-            };
+            let idx = row_index(&diff.0);
 
             let field_str = EVENT_FIELD_STR[idx];
             evt_field_mod_tracker[idx] = true;
@@ -348,16 +917,20 @@ mod stringify {
 
             rows[idx] = match diff_type {
                 ChangeType::Deletion => {
-                    let value = extract_evt_field_as_str(&diff.0, event_diff.old)?;
+                    let value =
+                        extract_evt_field_as_str(&diff.0, event_diff.old, wrap_description)?;
                     [field_str.to_string(), value, "None".to_string()]
                 }
                 ChangeType::Insertion => {
-                    let value = extract_evt_field_as_str(&diff.0, event_diff.new)?;
+                    let value =
+                        extract_evt_field_as_str(&diff.0, event_diff.new, wrap_description)?;
                     [field_str.to_string(), "None".to_string(), value]
                 }
                 ChangeType::Modification => {
-                    let old_value = extract_evt_field_as_str(&diff.0, event_diff.old)?;
-                    let new_value = extract_evt_field_as_str(&diff.0, event_diff.new)?;
+                    let old_value =
+                        extract_evt_field_as_str(&diff.0, event_diff.old, wrap_description)?;
+                    let new_value =
+                        extract_evt_field_as_str(&diff.0, event_diff.new, wrap_description)?;
                     [field_str.to_string(), old_value, new_value]
                 }
             };
@@ -366,33 +939,94 @@ mod stringify {
         Ok((rows, evt_field_mod_tracker))
     }
 
-    fn extract_evt_field_as_str(event_field: &EventField, event: &Event) -> Result<String> {
+    pub fn extract_evt_field_as_str(
+        event_field: &EventField,
+        event: &Event,
+        wrap_description: bool,
+    ) -> Result<String> {
         let value = match event_field {
-            EventField::DateEnd => {
-                let (end_date, end_time) = date_to_str(&event.get_end().unwrap())?;
-                format!("{end_date} {end_time}")
-            }
+            EventField::DateEnd => match event.get_end() {
+                Some(end) => {
+                    let (end_date, end_time) = date_to_str(&end)?;
+                    format!("{end_date} {end_time}")
+                }
+                None => "None".to_string(),
+            },
 
-            EventField::DateStart => {
-                let (start_date, start_time) = date_to_str(&event.get_start().unwrap())?;
-                format!("{start_date} {start_time}")
-            }
+            EventField::DateStart => match event.get_start() {
+                Some(start) => {
+                    let (start_date, start_time) = date_to_str(&start)?;
+                    format!("{start_date} {start_time}")
+                }
+                None => "None".to_string(),
+            },
 
             EventField::Description => {
-                let raw_str = event.get_description().unwrap().to_string();
-                trim_description(raw_str.as_str())
+                let raw_str = event.get_description().unwrap_or("None");
+                if wrap_description {
+                    trim_description(raw_str)
+                } else {
+                    raw_str.to_string()
+                }
             }
 
-            EventField::Location => event.get_location().unwrap().to_string(),
-            EventField::Priority => format!("{}", event.get_priority().unwrap()),
-            EventField::Summary => event.get_summary().unwrap().to_string(),
+            EventField::Location => event.get_location().unwrap_or("None").to_string(),
+            EventField::Priority => match event.get_priority() {
+                Some(priority) => priority.to_string(),
+                None => "None".to_string(),
+            },
+            EventField::Summary => event.get_summary().unwrap_or("No Heading").to_string(),
         };
 
         Ok(value)
     }
 
-    pub fn event_to_string(event: &Event) -> Result<String> {
-        let fields = get_event_fields(event)?;
+    pub fn event_to_format(event: &Event, format: MessageFormat) -> Result<String> {
+        match format {
+            MessageFormat::AsciiTable => event_to_string_table(event),
+            MessageFormat::Html => event_to_html(event),
+            MessageFormat::Markdown => event_to_markdown(event),
+            MessageFormat::Plain => event_to_string(event),
+        }
+    }
+
+    fn event_to_html(event: &Event) -> Result<String> {
+        let fields = get_event_fields(event, false)?;
+
+        let mut result = String::from("<table>\n");
+        result.push_str(&format!(
+            "<tr><th colspan=\"2\">{}</th></tr>\n",
+            escape_html(&fields[0])
+        ));
+
+        for (label, field) in EVENT_STR.iter().zip(&fields).skip(1) {
+            result.push_str(&format!(
+                "<tr><td>{label}</td><td>{}</td></tr>\n",
+                escape_html(field)
+            ));
+        }
+
+        result.push_str("</table>\n");
+        Ok(result)
+    }
+
+    fn event_to_markdown(event: &Event) -> Result<String> {
+        let fields = get_event_fields(event, false)?;
+
+        let mut result = format!("**{}**\n\n", escape_markdown(fields[0].trim_end()));
+
+        for (label, field) in EVENT_STR.iter().zip(&fields).skip(1) {
+            result.push_str(&format!(
+                "- **{label}:** {}\n",
+                escape_markdown(field.trim_end())
+            ));
+        }
+
+        Ok(result)
+    }
+
+    fn event_to_string(event: &Event) -> Result<String> {
+        let fields = get_event_fields(event, true)?;
 
         let mut result = String::new();
 
@@ -404,8 +1038,9 @@ mod stringify {
         Ok(result)
     }
 
-    pub fn event_to_string_table(event: &Event) -> Result<String> {
-        let [summary, date, start, end, location, priority, description] = get_event_fields(event)?;
+    fn event_to_string_table(event: &Event) -> Result<String> {
+        let [summary, date, start, end, location, priority, description] =
+            get_event_fields(event, true)?;
 
         let mut table = Table::new();
 
@@ -420,7 +1055,7 @@ mod stringify {
         Ok(table.to_string())
     }
 
-    fn get_event_fields(event: &Event) -> Result<[String; 7]> {
+    fn get_event_fields(event: &Event, wrap_description: bool) -> Result<[String; 7]> {
         let summary = String::from(event.get_summary().unwrap_or("No Heading"));
         let (date, start) = match event.get_start() {
             Some(d) => date_to_str(&d)?,
@@ -436,15 +1071,103 @@ mod stringify {
             None => "None".to_string(),
         };
         let description = event.get_description().unwrap_or("None");
-        let description = trim_description(description);
+        let description = if wrap_description {
+            trim_description(description)
+        } else {
+            description.to_string()
+        };
 
         Ok([summary, date, start, end, location, priority, description])
     }
 
+    /// Renders a word level diff of two texts. Unchanged lines of multi-line texts are
+    /// collapsed, so only the lines containing changes remain visible.
+    pub fn inline_diff(old: &str, new: &str, format: MessageFormat) -> String {
+        let mut lines: Vec<Vec<(ChangeTag, String)>> = vec![Vec::new()];
+
+        for change in TextDiff::from_words(old, new).iter_all_changes() {
+            let tag = change.tag();
+            // Lines follow the new text, removed line breaks stay visible inside their line
+            let value = match tag {
+                ChangeTag::Delete => change.value().replace('\n', DELETED_LINE_BREAK),
+                _ => change.value().to_string(),
+            };
+
+            for (idx, part) in value.split('\n').enumerate() {
+                if idx > 0 {
+                    lines.push(Vec::new());
+                }
+                if part.is_empty() {
+                    continue;
+                }
+
+                let line = lines.last_mut().unwrap();
+                match line.last_mut() {
+                    Some((last_tag, text)) if *last_tag == tag => text.push_str(part),
+                    _ => line.push((tag, part.to_string())),
+                }
+            }
+        }
+
+        while lines.len() > 1 && lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+
+        let (line_separator, omission) = match format {
+            MessageFormat::Html => ("<br>", "…"),
+            MessageFormat::Markdown => ("  \n  ", "…"),
+            MessageFormat::AsciiTable | MessageFormat::Plain => ("\n", "[...]"),
+        };
+
+        // Unchanged lines are only shown as context next to a changed line
+        let changed: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.iter().any(|(tag, _)| *tag != ChangeTag::Equal))
+            .map(|(idx, _)| idx)
+            .collect();
+        let shown = |idx: usize| {
+            lines.len() == 1
+                || changed
+                    .iter()
+                    .any(|changed_idx| changed_idx.abs_diff(idx) <= DIFF_CONTEXT_LINES)
+        };
+
+        let mut rendered_lines = Vec::with_capacity(lines.len());
+        let mut omitted = false;
+
+        for (idx, line) in lines.iter().enumerate() {
+            if !shown(idx) {
+                if !omitted {
+                    rendered_lines.push(omission.to_string());
+                    omitted = true;
+                }
+                continue;
+            }
+            omitted = false;
+
+            let rendered: String = line
+                .iter()
+                .map(|(tag, text)| render_diff_segment(*tag, text, format))
+                .collect();
+            rendered_lines.push(rendered);
+        }
+
+        rendered_lines.join(line_separator)
+    }
+
+    pub fn field_name(event_field: &EventField) -> &'static str {
+        match event_field {
+            EventField::Summary => "summary",
+            field => EVENT_FIELD_STR[row_index(field)],
+        }
+    }
+
     pub fn insert_unmodified_event_fields(
         event: &Event,
-        event_fields: &mut Vec<[String; 3]>,
+        event_fields: &mut [[String; 3]],
         event_field_mod_tracker: [bool; 6],
+        wrap_description: bool,
     ) -> Result<()> {
         for (idx, field) in event_field_mod_tracker.iter().enumerate() {
             if *field {
@@ -462,14 +1185,56 @@ mod stringify {
                 _ => None,
             };
 
-            let value = extract_evt_field_as_str(&event_field.unwrap(), event)?;
+            let value = extract_evt_field_as_str(&event_field.unwrap(), event, wrap_description)?;
             event_fields[idx] = [field_str.to_string(), value.clone(), value.clone()];
         }
 
         Ok(())
     }
 
-    fn trim_description(description: &str) -> String {
+    fn render_diff_segment(tag: ChangeTag, text: &str, format: MessageFormat) -> String {
+        let escape = |value: &str| match format {
+            MessageFormat::Html => escape_html(value),
+            MessageFormat::Markdown => escape_markdown(value),
+            MessageFormat::AsciiTable | MessageFormat::Plain => value.to_string(),
+        };
+
+        let trimmed = text.trim();
+        if tag == ChangeTag::Equal || trimmed.is_empty() {
+            return if tag == ChangeTag::Delete {
+                String::new()
+            } else {
+                escape(text)
+            };
+        }
+
+        // Keep surrounding whitespace outside of the markers, markdown would not render them otherwise
+        let leading = &text[..text.len() - text.trim_start().len()];
+        let trailing = &text[text.trim_end().len()..];
+        let marked = match (format, tag) {
+            (MessageFormat::Html, ChangeTag::Delete) => format!("<del>{}</del>", escape(trimmed)),
+            (MessageFormat::Html, _) => format!("<ins>{}</ins>", escape(trimmed)),
+            (MessageFormat::Markdown, ChangeTag::Delete) => format!("~~{}~~", escape(trimmed)),
+            (MessageFormat::Markdown, _) => format!("**{}**", escape(trimmed)),
+            (_, ChangeTag::Delete) => format!("[-{trimmed}-]"),
+            (_, _) => format!("{{+{trimmed}+}}"),
+        };
+
+        format!("{leading}{marked}{trailing}")
+    }
+
+    pub fn row_index(event_field: &EventField) -> usize {
+        match event_field {
+            EventField::DateEnd => 2,
+            EventField::DateStart => 1,
+            EventField::Description => 5,
+            EventField::Location => 3,
+            EventField::Priority => 4,
+            EventField::Summary => 0,
+        }
+    }
+
+    pub fn trim_description(description: &str) -> String {
         let mut new_description = String::new();
 
         for line in description.lines() {
@@ -488,3 +1253,272 @@ mod stringify {
         new_description
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn event(uid: &str, summary: &str, day: u32, location: &str) -> Event {
+        let start = NaiveDate::from_ymd_opt(2026, 11, day)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        Event::new()
+            .uid(uid)
+            .summary(summary)
+            .starts(start)
+            .ends(start + Duration::hours(1))
+            .location(location)
+            .done()
+    }
+
+    fn weekly_event(weeks_ago: i64, rule: &str) -> Event {
+        let start = (Local::now() - Duration::weeks(weeks_ago)).naive_local();
+        Event::new()
+            .uid("weekly")
+            .summary("Lecture")
+            .starts(start)
+            .ends(start + Duration::hours(1))
+            .add_property("RRULE", rule)
+            .done()
+    }
+
+    fn calendar(events: Vec<Event>) -> Calendar {
+        let mut calendar = Calendar::new();
+        for event in events {
+            calendar.push(event);
+        }
+        calendar
+    }
+
+    #[test]
+    fn regenerated_uid_of_unchanged_event_is_no_change() {
+        let old = calendar(vec![event("a", "Lecture", 2, "Room 1")]);
+        let new = calendar(vec![event("b", "Lecture", 2, "Room 1")]);
+
+        let diff = diff_calendars(&old, &new, Some(0.9)).unwrap();
+
+        assert!(diff.deletions.is_empty());
+        assert!(diff.insertions.is_empty());
+        assert!(diff.modifications.is_empty());
+    }
+
+    #[test]
+    fn regenerated_uid_of_changed_event_is_modification() {
+        let old = calendar(vec![event("a", "Lecture", 2, "Room 1")]);
+        let new = calendar(vec![event("b", "Lecture", 2, "Room 2")]);
+
+        let diff = diff_calendars(&old, &new, Some(0.7)).unwrap();
+
+        assert!(diff.deletions.is_empty());
+        assert!(diff.insertions.is_empty());
+        assert_eq!(diff.modifications.len(), 1);
+        assert_eq!(diff.modifications[0].0.get_uid(), Some("a"));
+        assert_eq!(diff.modifications[0].1.get_uid(), Some("b"));
+    }
+
+    #[test]
+    fn events_below_threshold_are_not_paired() {
+        let old = calendar(vec![event("a", "Lecture", 2, "Room 1")]);
+        let new = calendar(vec![event("b", "Exam", 3, "Hall")]);
+
+        let diff = diff_calendars(&old, &new, Some(0.5)).unwrap();
+
+        assert_eq!(diff.deletions.len(), 1);
+        assert_eq!(diff.insertions.len(), 1);
+        assert!(diff.modifications.is_empty());
+    }
+
+    #[test]
+    fn without_threshold_regenerated_uids_are_not_paired() {
+        let old = calendar(vec![event("a", "Lecture", 2, "Room 1")]);
+        let new = calendar(vec![event("b", "Lecture", 2, "Room 1")]);
+
+        let diff = diff_calendars(&old, &new, None).unwrap();
+
+        assert_eq!(diff.deletions.len(), 1);
+        assert_eq!(diff.insertions.len(), 1);
+    }
+
+    #[test]
+    fn events_are_paired_with_their_most_similar_counterpart() {
+        let old = calendar(vec![
+            event("a", "Lecture", 2, "Room 1"),
+            event("b", "Lecture", 3, "Room 1"),
+        ]);
+        let new = calendar(vec![
+            event("c", "Lecture", 3, "Room 2"),
+            event("d", "Lecture", 2, "Room 2"),
+        ]);
+
+        let diff = diff_calendars(&old, &new, Some(0.5)).unwrap();
+
+        assert!(diff.deletions.is_empty());
+        assert!(diff.insertions.is_empty());
+        let mut pairs: Vec<(&str, &str)> = diff
+            .modifications
+            .iter()
+            .map(|(old, new)| (old.get_uid().unwrap(), new.get_uid().unwrap()))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![("a", "d"), ("b", "c")]);
+    }
+
+    #[test]
+    fn ongoing_series_is_inside_the_window() {
+        let now = Local::now();
+        let event = weekly_event(20, "FREQ=WEEKLY");
+
+        assert!(event_in_window(&event, now, None, true));
+        assert!(event_in_window(
+            &event,
+            now,
+            Some(now + Duration::days(7)),
+            true
+        ));
+    }
+
+    #[test]
+    fn ended_series_is_outside_the_window() {
+        let now = Local::now();
+        let event = weekly_event(20, "FREQ=WEEKLY;COUNT=3");
+
+        assert!(!event_in_window(&event, now, None, true));
+        assert!(event_in_window(&event, now, None, false));
+    }
+
+    #[test]
+    fn past_single_event_is_outside_the_window() {
+        let now = Local::now();
+        let start = (now - Duration::days(3)).naive_local();
+        let event = Event::new()
+            .uid("single")
+            .starts(start)
+            .ends(start + Duration::hours(1))
+            .done();
+
+        assert!(!event_in_window(&event, now, None, true));
+        assert!(event_in_window(&event, now, None, false));
+    }
+
+    #[test]
+    fn inline_diff_keeps_changed_lines_and_context() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix";
+        let new = "one\ntwo\nthree\nFOUR\nfive\nsix";
+
+        let diff = stringify::inline_diff(old, new, MessageFormat::Plain);
+
+        assert_eq!(diff, "[...]\nthree\n[-four-]{+FOUR+}\nfive\n[...]");
+    }
+
+    #[test]
+    fn inline_diff_shows_removed_line_breaks() {
+        let old = "first line\nsecond line\nthird\nfourth\nfifth";
+        let new = "first line second line\nthird\nfourth\nfifth";
+
+        let diff = stringify::inline_diff(old, new, MessageFormat::Plain);
+
+        assert_eq!(diff, "first line[-¶-] second line\nthird\n[...]");
+    }
+
+    fn default_ignored() -> Vec<String> {
+        DEFAULT_IGNORED_PROPERTIES
+            .iter()
+            .map(|property| property.to_string())
+            .collect()
+    }
+
+    static BASE_ICS: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:test\r\n\
+        BEGIN:VEVENT\r\nUID:a\r\nDTSTAMP:20250101T000000Z\r\nDTSTART:20250106T100000Z\r\nSUMMARY:Lecture\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:b\r\nDTSTAMP:20250101T000000Z\r\nDTSTART:20250107T100000Z\r\nSUMMARY:Seminar\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn folded_lines_are_equivalent() {
+        let folded = BASE_ICS.replace("SUMMARY:Lecture", "SUMMARY:Lec\r\n ture");
+
+        assert!(ics_equivalent(BASE_ICS, &folded, &default_ignored()).unwrap());
+    }
+
+    #[test]
+    fn reordered_properties_and_components_are_equivalent() {
+        let reordered = "BEGIN:VCALENDAR\r\nPRODID:test\r\nVERSION:2.0\r\n\
+            BEGIN:VEVENT\r\nSUMMARY:Seminar\r\nDTSTART:20250107T100000Z\r\nUID:b\r\nDTSTAMP:20250101T000000Z\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nDTSTART:20250106T100000Z\r\nUID:a\r\nSUMMARY:Lecture\r\nDTSTAMP:20250101T000000Z\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        assert!(ics_equivalent(BASE_ICS, reordered, &default_ignored()).unwrap());
+    }
+
+    #[test]
+    fn changes_of_ignored_properties_are_equivalent() {
+        let changed = BASE_ICS
+            .replace("DTSTAMP:20250101T000000Z", "DTSTAMP:20250301T120000Z")
+            .replace(
+                "SUMMARY:Seminar",
+                "SUMMARY:Seminar\r\nX-PROVIDER-ID:42\r\nSEQUENCE:3",
+            );
+
+        assert!(ics_equivalent(BASE_ICS, &changed, &default_ignored()).unwrap());
+        assert!(!ics_equivalent(BASE_ICS, &changed, &[]).unwrap());
+    }
+
+    #[test]
+    fn summary_changes_are_not_equivalent() {
+        let changed = BASE_ICS.replace("SUMMARY:Lecture", "SUMMARY:Lecture cancelled");
+
+        assert!(!ics_equivalent(BASE_ICS, &changed, &default_ignored()).unwrap());
+    }
+
+    fn report(
+        summary: &str,
+        old_location: &str,
+        new_location: &str,
+        format: MessageFormat,
+    ) -> DiffReport {
+        let diff = CalendarDiff {
+            calendar_name: "Work".to_string(),
+            deletions: Vec::new(),
+            insertions: vec![event("a", summary, 2, "Room 1")],
+            modifications: vec![(
+                event("b", summary, 3, old_location),
+                event("b", summary, 3, new_location),
+            )],
+        };
+        generate_diff_report(&diff, format).unwrap()
+    }
+
+    #[test]
+    fn html_reports_escape_event_fields() {
+        let report = report(
+            "Tom & Jerry <live>",
+            "<b>A</b>",
+            "B & C",
+            MessageFormat::Html,
+        );
+
+        assert!(report.insertions[0].contains("Tom &amp; Jerry &lt;live&gt;"));
+        assert!(!report.insertions[0].contains("<live>"));
+        assert!(report.modifications[0].contains("Tom &amp; Jerry &lt;live&gt;"));
+        assert!(report.modifications[0].contains("&lt;b&gt;A&lt;/b&gt;"));
+        assert!(report.modifications[0].contains("B &amp; C"));
+        assert!(!report.modifications[0].contains("<b>A"));
+    }
+
+    #[test]
+    fn markdown_reports_escape_event_fields() {
+        let report = report(
+            "*Launch* [beta] #1 _now_",
+            "Room | 1",
+            "Room ` 2",
+            MessageFormat::Markdown,
+        );
+
+        let escaped = r"\*Launch\* \[beta\] \#1 \_now\_";
+        assert!(report.insertions[0].contains(escaped));
+        assert!(report.modifications[0].contains(escaped));
+        assert!(report.modifications[0].contains(r"\|"));
+        assert!(report.modifications[0].contains(r"\`"));
+    }
+}
@@ -146,9 +146,8 @@ pub fn initialize_repo(config: &GitConfig) -> Result<()> {
         return Ok(());
     }
 
-    if config.remote.is_some() {
-        clone_repo(&config.remote.clone().unwrap())
-            .with_context(|| "Failed to clone repository")?;
+    if let Some(remote) = &config.remote {
+        clone_repo(remote).with_context(|| "Failed to clone repository")?;
     } else {
         Repository::init(repo_path).with_context(|| "Failed to initialize repository")?;
     }
@@ -187,8 +186,7 @@ pub fn update_repo(calendar_names: &Vec<String>, config: GitConfig) -> Result<()
         commit(&msg, &repository, &signature).with_context(|| "Failed to commit")?;
     }
 
-    if config.remote.is_some() {
-        let remote_cfg = &config.remote.unwrap();
+    if let Some(remote_cfg) = &config.remote {
         push_to_remote(&repository, &remote_cfg.username, &remote_cfg.token)
             .with_context(|| "Failed to push to remote")?;
     }
@@ -1,20 +1,33 @@
+use crate::agenda::push_agendas;
+use crate::alert::push_alerts;
 use crate::cache::{copy_from_cache, initialize_cache};
-use crate::calendar::run_pipeline;
+use crate::calendar::{DroppedReport, PipelineOutput, run_pipeline};
 use crate::config::{Config, load_config};
 use crate::git_repo::{initialize_repo, update_repo};
-use crate::notification::push_notifications;
+use crate::notification::{
+    push_dropped_notifications, push_import_notifications, push_notifications,
+};
+use crate::reminder::push_reminders;
+use crate::rules::{print_rule_status, push_rule_warnings};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use chrono::Local;
-use std::fs::{File, create_dir};
+use std::fs::{File, create_dir, write};
 use std::path::Path;
 
+mod agenda;
+mod alert;
 mod cache;
 mod calendar;
 mod config;
 mod diff;
 mod git_repo;
 mod notification;
+mod outbox;
+mod recurrence;
+mod reminder;
+mod rules;
+mod schedule;
 
 static INIT_MARKER: &str = ".initialized";
 static SERVING_DIRECTORY: &str = "calendar_serving";
@@ -23,10 +36,16 @@ fn already_initialized() -> bool {
     Path::new(INIT_MARKER).exists()
 }
 
+fn create_serving_directory() -> Result<()> {
+    if !Path::new(SERVING_DIRECTORY).exists() {
+        create_dir(SERVING_DIRECTORY)?;
+    }
+    Ok(())
+}
+
 fn initialize(config: &Config) -> Result<()> {
-    if config.git.is_some() {
-        let git_cfg = config.clone().git.unwrap();
-        initialize_repo(&git_cfg)?;
+    if let Some(git_cfg) = &config.git {
+        initialize_repo(git_cfg)?;
     }
 
     initialize_cache(config)?;
@@ -35,23 +54,22 @@ fn initialize(config: &Config) -> Result<()> {
     Ok(())
 }
 
-fn update_serving_directory(filenames: &Vec<String>) -> Result<()> {
-    if !Path::new(SERVING_DIRECTORY).exists() {
-        create_dir(SERVING_DIRECTORY)?;
-    }
+/// Publishes the events removed from every calendar, even if the calendar did not change
+fn publish_dropped_reports(reports: &[DroppedReport]) -> Result<()> {
+    create_serving_directory()?;
 
-    for filename in filenames {
-        let src = format!("{filename}_filtered.ics");
-        let dest = format!("{SERVING_DIRECTORY}/{filename}.ics");
-        copy_from_cache(&src, &dest)?;
+    for report in reports {
+        let data = serde_json::to_string_pretty(report)
+            .with_context(|| "Failed to serialize dropped events report!")?;
+        let dest = format!("{SERVING_DIRECTORY}/{}_dropped.json", report.calendar_name);
+        write(&dest, data).with_context(|| format!("Failed to write file '{dest}'!"))?;
     }
 
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let config = load_config()?;
-
+/// Filters the calendars, publishes the changes and sends notifications
+fn run(config: Config) -> Result<()> {
     if !already_initialized() {
         println!(
             "[{}] Initializing...",
@@ -64,13 +82,16 @@ fn main() -> Result<()> {
         "[{}] Running pipeline...",
         Local::now().format("%Y-%m-%dT%H:%M:%S")
     );
-    let (updated_files, reports) = run_pipeline(&config)?;
+    let output = run_pipeline(&config)?;
+    publish_dropped_reports(&output.dropped_reports)?;
 
-    if updated_files.is_empty() {
+    if output.updated_names.is_empty() {
         println!(
             "[{}] No changes detected.",
             Local::now().format("%Y-%m-%dT%H:%M:%S")
         );
+        // Retry notifications that failed in previous runs
+        send_notifications(&config, &output);
         return Ok(());
     }
 
@@ -83,17 +104,101 @@ fn main() -> Result<()> {
         "[{}] Updating serving directory.",
         Local::now().format("%Y-%m-%dT%H:%M:%S")
     );
-    update_serving_directory(&updated_files)?;
-    push_notifications(&config, &updated_files, reports)?;
+    update_serving_directory(&output.updated_names)?;
+    send_notifications(&config, &output);
 
-    if config.git.is_some() {
-        let git_cfg = config.git.unwrap();
+    if let Some(git_cfg) = config.git {
         println!(
             "[{}] Updating git repo.",
             Local::now().format("%Y-%m-%dT%H:%M:%S")
         );
-        update_repo(&updated_files, git_cfg)?;
+        update_repo(&output.updated_names, git_cfg)?;
+    }
+
+    Ok(())
+}
+
+fn send_alerts(config: &Config, result: &Result<()>) {
+    if let Err(e) = push_alerts(config, result) {
+        println!(
+            "[{}] Failed to send alerts: {e:#}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+}
+
+/// Notification failures are only logged, pending deliveries are retried by the next run
+fn send_notifications(config: &Config, output: &PipelineOutput) {
+    if let Err(e) = push_import_notifications(config, &output.imports) {
+        println!(
+            "[{}] Failed to send import summaries: {e:#}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+
+    // Only the reports of updated calendars are sent, the others did not change
+    let updated_reports: Vec<&DroppedReport> = output
+        .dropped_reports
+        .iter()
+        .filter(|report| {
+            output
+                .diffs
+                .iter()
+                .any(|diff| diff.calendar_name == report.calendar_name)
+        })
+        .collect();
+    if let Err(e) = push_dropped_notifications(config, &updated_reports) {
+        println!(
+            "[{}] Failed to send dropped events: {e:#}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+
+    if let Err(e) = push_notifications(config, &output.diffs) {
+        println!(
+            "[{}] Failed to send notifications: {e:#}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+
+    if let Err(e) = push_agendas(config) {
+        println!(
+            "[{}] Failed to send agendas: {e:#}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+
+    if let Err(e) = push_rule_warnings(config) {
+        println!(
+            "[{}] Failed to check filter rules: {e:#}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+}
+
+fn update_serving_directory(filenames: &Vec<String>) -> Result<()> {
+    create_serving_directory()?;
+
+    for filename in filenames {
+        let src = format!("{filename}_filtered.ics");
+        let dest = format!("{SERVING_DIRECTORY}/{filename}.ics");
+        copy_from_cache(&src, &dest)?;
     }
 
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let config = load_config()?;
+
+    match std::env::args().nth(1).as_deref() {
+        None => {
+            let result = run(config.clone());
+            send_alerts(&config, &result);
+            result
+        }
+        Some("reminders") => push_reminders(&config),
+        Some("status") => print_rule_status(&config),
+        Some(command) => bail!("Unknown command '{command}'!"),
+    }
+}
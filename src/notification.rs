@@ -1,48 +1,1598 @@
-use crate::config::{Config, EmailConfig, GotifyConfig};
-use crate::diff::DiffReport;
+use crate::agenda::Agenda;
+use crate::alert::Alert;
+use crate::calendar::{DroppedReport, ImportSummary, calendar_name};
+use crate::config::{
+    Channel, Config, DigestMode, EmailConfig, GotifyConfig, IncomingWebhookConfig,
+    InvitationMethod, MatrixConfig, MessageFormat, NotificationConfig, NotificationRoutingConfig,
+    NtfyConfig, SmtpTls, TelegramConfig, TemplateConfig, WebhookConfig,
+};
+use crate::diff::{
+    CalendarChanges, CalendarDiff, ChangeType, DiffReport, EventData, calendar_changes,
+    escape_html, escape_markdown, field_changes, generate_diff_report,
+};
+use crate::outbox::{OutboxEntry, load_outbox, new_entry_id, process_outbox, save_outbox};
+use crate::reminder::Reminder;
+use crate::rules::StaleRules;
+use crate::schedule::{RoutedDiffs, ScheduleState, load_schedule_state, save_schedule_state};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
 use chrono::Local;
-use lettre::message::{Mailbox, Mailboxes, header::ContentType};
+use hmac::{Hmac, Mac};
+use icalendar::{Calendar, Component, Event, EventStatus, Property};
+use lettre::message::header::{Bcc, Cc, ContentType, To};
+use lettre::message::{Attachment, Mailbox, Mailboxes, MultiPart, SinglePart};
 use lettre::transport::smtp::{SmtpTransport, authentication::Credentials};
 use lettre::{Message, Transport};
-use reqwest::blocking::Client;
+use minijinja::{AutoEscape, Environment, Value as TemplateValue, context};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::thread::sleep;
+use std::time::Duration;
 use url::Url;
 
+static DEFAULT_SIGNATURE_HEADER: &str = "X-Signature-256";
+static DEFAULT_TELEGRAM_API_URL: &str = "https://api.telegram.org";
+static DEFAULT_WEBHOOK_RETRIES: u32 = 3;
+static DISCORD_EMBED_DESCRIPTION_LIMIT: usize = 4096;
+static DISCORD_EMBED_TITLE_LIMIT: usize = 256;
+static DISCORD_EMBEDS_PER_MESSAGE: usize = 10;
+static DISCORD_MESSAGE_LIMIT: usize = 6000;
+static DROPPED_EVENTS_LIMIT: usize = 20;
+static EMAIL_HTML_STYLE: &str = "body { font-family: sans-serif; font-size: 14px; color: #212529; } \
+    table { border-collapse: collapse; margin: 0 0 16px 0; min-width: 320px; } \
+    th { background-color: #e9ecef; text-align: left; } \
+    th, td { border: 1px solid #dee2e6; padding: 4px 8px; vertical-align: top; } \
+    del { background-color: #f8d7da; color: #842029; } \
+    ins { background-color: #d1e7dd; color: #0f5132; text-decoration: none; }";
+/// Channels receiving standalone messages like reminders and alerts, webhooks only receive changes
+static MESSAGE_CHANNELS: [Channel; 8] = [
+    Channel::Discord,
+    Channel::Email,
+    Channel::Gotify,
+    Channel::Matrix,
+    Channel::Mattermost,
+    Channel::Ntfy,
+    Channel::Slack,
+    Channel::Telegram,
+];
+static MATTERMOST_MESSAGE_LIMIT: usize = 16383;
+static MAX_RETRY_DELAY_SECONDS: u64 = 60;
+static SLACK_BLOCKS_PER_MESSAGE: usize = 50;
+static SLACK_HEADER_LIMIT: usize = 150;
+static SLACK_SECTION_LIMIT: usize = 3000;
+static TELEGRAM_MESSAGE_LIMIT: usize = 4096;
+
+/// Rendered messages of one channel, persisted in the outbox until they are delivered.
+/// Recipients are `None` if the ones of the channel config are used.
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "channel", rename_all = "kebab-case")]
+enum Delivery {
+    Discord {
+        messages: Vec<Notification>,
+    },
+    Email {
+        html_messages: Vec<Notification>,
+        /// Calendar attachments of each message
+        #[serde(default)]
+        invitations: Vec<Vec<Invitation>>,
+        plain_messages: Vec<Notification>,
+        recipients: Option<Vec<String>>,
+    },
+    Gotify {
+        format: MessageFormat,
+        messages: Vec<Notification>,
+    },
+    Matrix {
+        html_messages: Vec<Notification>,
+        plain_messages: Vec<Notification>,
+        room_ids: Option<Vec<String>>,
+    },
+    Mattermost {
+        messages: Vec<Notification>,
+    },
+    Ntfy {
+        format: MessageFormat,
+        messages: Vec<Notification>,
+    },
+    Slack {
+        messages: Vec<Notification>,
+    },
+    Telegram {
+        chat_ids: Option<Vec<String>>,
+        messages: Vec<Notification>,
+    },
+    Webhook {
+        body: String,
+    },
+}
+
+impl Delivery {
+    fn channel(&self) -> &'static str {
+        match self {
+            Delivery::Discord { .. } => "discord",
+            Delivery::Email { .. } => "email",
+            Delivery::Gotify { .. } => "gotify",
+            Delivery::Matrix { .. } => "matrix",
+            Delivery::Mattermost { .. } => "mattermost",
+            Delivery::Ntfy { .. } => "ntfy",
+            Delivery::Slack { .. } => "slack",
+            Delivery::Telegram { .. } => "telegram",
+            Delivery::Webhook { .. } => "webhook",
+        }
+    }
+
+    /// Splits the delivery into one delivery per message and room or chat, so retrying a failed
+    /// send does not repeat the ones that already went out
+    fn split(self, config: &NotificationConfig) -> Vec<Delivery> {
+        let single = |messages: Vec<Notification>| messages.into_iter().map(|msg| vec![msg]);
+
+        match self {
+            Delivery::Discord { messages } => single(messages)
+                .map(|messages| Delivery::Discord { messages })
+                .collect(),
+            Delivery::Email {
+                html_messages,
+                invitations,
+                plain_messages,
+                recipients,
+            } => {
+                let mut invitations = invitations.into_iter();
+                html_messages
+                    .into_iter()
+                    .zip(plain_messages)
+                    .map(|(html, plain)| Delivery::Email {
+                        html_messages: vec![html],
+                        invitations: invitations.next().into_iter().collect(),
+                        plain_messages: vec![plain],
+                        recipients: recipients.clone(),
+                    })
+                    .collect()
+            }
+            Delivery::Gotify { format, messages } => single(messages)
+                .map(|messages| Delivery::Gotify { format, messages })
+                .collect(),
+            Delivery::Matrix {
+                html_messages,
+                plain_messages,
+                room_ids,
+            } => {
+                let configured = config.matrix.as_ref().map(|config| &config.room_ids);
+                split_targets(room_ids, configured)
+                    .into_iter()
+                    .flat_map(|room_ids| {
+                        html_messages
+                            .iter()
+                            .zip(&plain_messages)
+                            .map(move |(html, plain)| Delivery::Matrix {
+                                html_messages: vec![html.clone()],
+                                plain_messages: vec![plain.clone()],
+                                room_ids: room_ids.clone(),
+                            })
+                    })
+                    .collect()
+            }
+            Delivery::Mattermost { messages } => single(messages)
+                .map(|messages| Delivery::Mattermost { messages })
+                .collect(),
+            Delivery::Ntfy { format, messages } => single(messages)
+                .map(|messages| Delivery::Ntfy { format, messages })
+                .collect(),
+            Delivery::Slack { messages } => single(messages)
+                .map(|messages| Delivery::Slack { messages })
+                .collect(),
+            Delivery::Telegram { chat_ids, messages } => {
+                let configured = config.telegram.as_ref().map(|config| &config.chat_ids);
+                split_targets(chat_ids, configured)
+                    .into_iter()
+                    .flat_map(|chat_ids| {
+                        messages.iter().map(move |msg| Delivery::Telegram {
+                            chat_ids: chat_ids.clone(),
+                            messages: vec![msg.clone()],
+                        })
+                    })
+                    .collect()
+            }
+            Delivery::Webhook { body } => vec![Delivery::Webhook { body }],
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Notification {
+    body: String,
+    /// Calendar the notification is about, `None` if it covers multiple calendars
+    calendar_name: Option<String>,
+    /// Kind of change the notification is about, `None` for digests
+    change_type: Option<ChangeType>,
+    /// Data available to user supplied templates
+    context: Value,
+    /// Changed events, the new version for modifications. Not persisted in the outbox
+    #[serde(skip)]
+    events: Vec<(ChangeType, Event)>,
+    title: String,
+}
+
+#[derive(Serialize)]
+struct GotifyMessage<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extras: Option<&'a Value>,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    title: &'a str,
+}
+
+/// iCalendar attachment of an email, see RFC 6047 (iMIP)
+#[derive(Clone, Deserialize, Serialize)]
+struct Invitation {
+    filename: String,
+    ics: String,
+    method: String,
+}
+
+#[derive(Serialize)]
+struct MatrixMessage<'a> {
+    body: &'a str,
+    format: &'a str,
+    formatted_body: &'a str,
+    msgtype: &'a str,
+}
+
+#[derive(Serialize)]
+struct NtfyMessage<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    click: Option<String>,
+    markdown: bool,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<&'a Vec<String>>,
+    title: &'a str,
+    topic: &'a str,
+}
+
+#[derive(Serialize)]
+struct TelegramMessage<'a> {
+    chat_id: &'a str,
+    disable_web_page_preview: bool,
+    parse_mode: &'a str,
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct WebhookPayload {
+    calendars: Vec<CalendarChanges>,
+    summary: String,
+    timestamp: String,
+}
+
+/// Renders the user supplied title and body templates of a channel. Next to the message specific
+/// data, templates can access the default `title`, the default rendered `report` and `run` metadata.
+fn apply_templates(
+    messages: &mut [Notification],
+    templates: &TemplateConfig,
+    format: MessageFormat,
+) -> Result<()> {
+    if templates.title_template.is_none() && templates.body_template.is_none() {
+        return Ok(());
+    }
+
+    let run = run_metadata();
+
+    for msg in messages {
+        let mut data = msg.context.clone();
+        data["run"] = run.clone();
+        data["title"] = json!(msg.title);
+        // The report is already rendered in the message format, so it must not be escaped again
+        let report = match format {
+            MessageFormat::Html => TemplateValue::from_safe_string(msg.body.clone()),
+            _ => TemplateValue::from(msg.body.clone()),
+        };
+        let context = context! { report, ..TemplateValue::from_serialize(&data) };
+
+        // Titles are plain text, every channel escapes them itself
+        if let Some(template) = &templates.title_template {
+            msg.title = render_template(template, &context, MessageFormat::Plain)
+                .with_context(|| "Failed to render title template")?
+                .trim()
+                .to_string();
+        }
+        if let Some(template) = &templates.body_template {
+            msg.body = render_template(template, &context, format)
+                .with_context(|| "Failed to render body template")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn build_agenda_message(agenda: &Agenda, format: MessageFormat) -> Notification {
+    let mut body = String::new();
+    let mut current_day = None;
+
+    for entry in &agenda.entries {
+        let day = entry.start.date_naive();
+        if current_day != Some(day) {
+            if format == MessageFormat::Html && current_day.is_some() {
+                body.push_str("</table>\n");
+            }
+            body.push_str(&digest_heading(
+                &day.format("%A, %Y-%m-%d").to_string(),
+                2,
+                format,
+            ));
+            if format == MessageFormat::Html {
+                body.push_str("<table>\n");
+            }
+            current_day = Some(day);
+        }
+
+        let time = if entry.all_day {
+            "all day".to_string()
+        } else {
+            format!(
+                "{} - {}",
+                entry.start.format("%H:%M"),
+                entry.end.format("%H:%M")
+            )
+        };
+        let line = match format {
+            MessageFormat::Html => format!(
+                "<tr><td>{time}</td><td><b>{}</b></td><td>{}</td></tr>\n",
+                escape_html(&entry.summary),
+                escape_html(entry.location.as_deref().unwrap_or_default())
+            ),
+            MessageFormat::Markdown => {
+                let location = match &entry.location {
+                    Some(location) => format!(" ({})", escape_markdown(location)),
+                    None => String::new(),
+                };
+                format!(
+                    "- {time}: **{}**{location}\n",
+                    escape_markdown(&entry.summary)
+                )
+            }
+            MessageFormat::AsciiTable | MessageFormat::Plain => {
+                let location = match &entry.location {
+                    Some(location) => format!(" ({location})"),
+                    None => String::new(),
+                };
+                format!("{time}: {}{location}\n", entry.summary)
+            }
+        };
+        body.push_str(&line);
+    }
+
+    if agenda.entries.is_empty() {
+        body.push_str("No events.\n");
+    } else if format == MessageFormat::Html {
+        body.push_str("</table>\n");
+    }
+
+    Notification {
+        body,
+        calendar_name: Some(agenda.calendar_name.clone()),
+        change_type: None,
+        context: Value::Null,
+        events: Vec::new(),
+        title: agenda.title.clone(),
+    }
+}
+
+fn build_alert_message(alert: &Alert, format: MessageFormat) -> Notification {
+    let since = alert.since.format("%Y-%m-%d %H:%M");
+    let (title, intro) = if alert.recovered {
+        (
+            "ICS Filter recovered".to_string(),
+            format!("Runs succeed again after failing since {since}. Last error:"),
+        )
+    } else {
+        (
+            "ICS Filter run failed".to_string(),
+            format!("Runs have been failing since {since}. Error:"),
+        )
+    };
+
+    let body = match format {
+        MessageFormat::Html => {
+            format!("<p>{intro}</p>\n<pre>{}</pre>\n", escape_html(&alert.error))
+        }
+        MessageFormat::Markdown => format!("{intro}\n\n```\n{}\n```\n", alert.error),
+        MessageFormat::AsciiTable | MessageFormat::Plain => {
+            format!("{intro}\n{}\n", alert.error)
+        }
+    };
+
+    Notification {
+        body,
+        calendar_name: None,
+        change_type: None,
+        context: Value::Null,
+        events: Vec::new(),
+        title,
+    }
+}
+
+/// Renders the messages of every configured channel for the calendars routed to it
+fn build_deliveries(
+    config: &Config,
+    notification_config: &NotificationConfig,
+    diffs: &[CalendarDiff],
+    schedule: &mut ScheduleState,
+) -> Result<Vec<Delivery>> {
+    let routes = calendar_routes(config)?;
+    let mut routed = |channel, schedule_config| {
+        schedule.release(
+            channel,
+            schedule_config,
+            route_diffs(&routes, diffs, channel),
+        )
+    };
+    let mut deliveries = Vec::new();
+
+    if let Some(email_config) = &notification_config.email {
+        for (recipients, diffs) in routed(Channel::Email, &email_config.schedule) {
+            // The plain text alternative uses the configured format unless it is HTML
+            let plain_format = match channel_format(notification_config, email_config.format) {
+                MessageFormat::Html => MessageFormat::Plain,
+                format => format,
+            };
+            let digest = email_config.digest.unwrap_or_default();
+            let templates = &email_config.templates;
+            let html_messages = build_messages(&diffs, MessageFormat::Html, digest, templates)?;
+            let invitations = match email_config.invitations {
+                Some(method) => {
+                    let organizer = email_sender(email_config)?;
+                    let attendees =
+                        parse_mailboxes(recipients.as_ref().unwrap_or(&email_config.recipients))
+                            .with_context(|| "Failed to parse recipient email addresses")?;
+                    html_messages
+                        .iter()
+                        .map(|msg| build_invitations(&msg.events, method, &organizer, &attendees))
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+            deliveries.push(Delivery::Email {
+                html_messages,
+                invitations,
+                plain_messages: build_messages(&diffs, plain_format, digest, templates)?,
+                recipients,
+            });
+        }
+    }
+
+    if let Some(gotify_config) = &notification_config.gotify {
+        for (_, diffs) in routed(Channel::Gotify, &gotify_config.schedule) {
+            let format = channel_format(notification_config, gotify_config.format);
+            let messages = build_messages(
+                &diffs,
+                format,
+                DigestMode::Disabled,
+                &gotify_config.templates,
+            )?;
+            deliveries.push(Delivery::Gotify { format, messages });
+        }
+    }
+
+    if let Some(matrix_config) = &notification_config.matrix {
+        for (room_ids, diffs) in routed(Channel::Matrix, &matrix_config.schedule) {
+            let digest = matrix_config.digest.unwrap_or_default();
+            let templates = &matrix_config.templates;
+            deliveries.push(Delivery::Matrix {
+                html_messages: build_messages(&diffs, MessageFormat::Html, digest, templates)?,
+                plain_messages: build_messages(&diffs, MessageFormat::Plain, digest, templates)?,
+                room_ids,
+            });
+        }
+    }
+
+    if let Some(ntfy_config) = &notification_config.ntfy {
+        for (_, diffs) in routed(Channel::Ntfy, &ntfy_config.schedule) {
+            let format = channel_format(notification_config, ntfy_config.format);
+            let digest = ntfy_config.digest.unwrap_or_default();
+            let messages = build_messages(&diffs, format, digest, &ntfy_config.templates)?;
+            deliveries.push(Delivery::Ntfy { format, messages });
+        }
+    }
+
+    if let Some(slack_config) = &notification_config.slack {
+        for (_, diffs) in routed(Channel::Slack, &slack_config.schedule) {
+            let digest = slack_config.digest.unwrap_or_default();
+            let messages = build_messages(
+                &diffs,
+                MessageFormat::Plain,
+                digest,
+                &slack_config.templates,
+            )?;
+            deliveries.push(Delivery::Slack { messages });
+        }
+    }
+
+    if let Some(discord_config) = &notification_config.discord {
+        for (_, diffs) in routed(Channel::Discord, &discord_config.schedule) {
+            let digest = discord_config.digest.unwrap_or_default();
+            let messages = build_messages(
+                &diffs,
+                MessageFormat::Markdown,
+                digest,
+                &discord_config.templates,
+            )?;
+            deliveries.push(Delivery::Discord { messages });
+        }
+    }
+
+    if let Some(mattermost_config) = &notification_config.mattermost {
+        for (_, diffs) in routed(Channel::Mattermost, &mattermost_config.schedule) {
+            let digest = mattermost_config.digest.unwrap_or_default();
+            let messages = build_messages(
+                &diffs,
+                MessageFormat::Markdown,
+                digest,
+                &mattermost_config.templates,
+            )?;
+            deliveries.push(Delivery::Mattermost { messages });
+        }
+    }
+
+    if let Some(telegram_config) = &notification_config.telegram {
+        for (chat_ids, diffs) in routed(Channel::Telegram, &telegram_config.schedule) {
+            let digest = telegram_config.digest.unwrap_or_default();
+            let messages = build_messages(
+                &diffs,
+                MessageFormat::Plain,
+                digest,
+                &telegram_config.templates,
+            )?;
+            deliveries.push(Delivery::Telegram { chat_ids, messages });
+        }
+    }
+
+    if let Some(webhook_config) = &notification_config.webhook {
+        for (_, diffs) in routed(Channel::Webhook, &webhook_config.schedule) {
+            if let Some(payload) = build_webhook_payload(&diffs)? {
+                let body = build_webhook_body(webhook_config, &payload)?;
+                deliveries.push(Delivery::Webhook { body });
+            }
+        }
+    }
+
+    Ok(deliveries)
+}
+
+fn build_digest(
+    diffs: &[CalendarDiff],
+    reports: &[DiffReport],
+    format: MessageFormat,
+) -> Result<Option<Notification>> {
+    let deletions: usize = reports.iter().map(|report| report.deletions.len()).sum();
+    let insertions: usize = reports.iter().map(|report| report.insertions.len()).sum();
+    let modifications: usize = reports
+        .iter()
+        .map(|report| report.modifications.len())
+        .sum();
+
+    if deletions + insertions + modifications == 0 {
+        return Ok(None);
+    }
+
+    let counts = summarize_counts(deletions, insertions, modifications);
+    let (title, calendar_name) = match reports {
+        [report] => (
+            format!("'{}' -- {counts}", report.calendar_name),
+            Some(report.calendar_name.clone()),
+        ),
+        _ => (format!("ICS Filter -- {counts}"), None),
+    };
+
+    let mut body = String::new();
+
+    for report in reports {
+        let sections = [
+            ("Deleted", &report.deletions),
+            ("Added", &report.insertions),
+            ("Modified", &report.modifications),
+        ];
+        if sections.iter().all(|(_, entries)| entries.is_empty()) {
+            continue;
+        }
+
+        body.push_str(&digest_heading(&report.calendar_name, 1, format));
+
+        for (label, entries) in sections {
+            if entries.is_empty() {
+                continue;
+            }
+
+            body.push_str(&digest_heading(
+                &format!("{label} ({})", entries.len()),
+                2,
+                format,
+            ));
+            for entry in entries {
+                body.push_str(entry);
+                body.push('\n');
+            }
+        }
+    }
+
+    let mut calendars = Vec::with_capacity(diffs.len());
+    for diff in diffs {
+        calendars.push(calendar_changes(diff)?);
+    }
+
+    let context = json!({
+        "calendar": calendar_name,
+        "calendars": calendars,
+        "counts": { "added": insertions, "deleted": deletions, "modified": modifications },
+        "summary": counts,
+    });
+
+    let mut events = Vec::with_capacity(deletions + insertions + modifications);
+    for diff in diffs {
+        events.extend(
+            diff.deletions
+                .iter()
+                .map(|event| (ChangeType::Deletion, event.clone())),
+        );
+        events.extend(
+            diff.insertions
+                .iter()
+                .map(|event| (ChangeType::Insertion, event.clone())),
+        );
+        events.extend(
+            diff.modifications
+                .iter()
+                .map(|(_, new)| (ChangeType::Modification, new.clone())),
+        );
+    }
+
+    Ok(Some(Notification {
+        body,
+        calendar_name,
+        change_type: None,
+        context,
+        events,
+        title,
+    }))
+}
+
+/// Lists the events removed from a calendar, events with the same summary and rule are grouped
+fn build_dropped_message(report: &DroppedReport, format: MessageFormat) -> Notification {
+    // Events of the same series share summary and rule, they are listed once with their count
+    let mut groups: Vec<(&str, &str, usize)> = Vec::new();
+    for event in &report.dropped {
+        match groups
+            .iter_mut()
+            .find(|(summary, rule, _)| *summary == event.summary && *rule == event.rule)
+        {
+            Some(group) => group.2 += 1,
+            None => groups.push((&event.summary, &event.rule, 1)),
+        }
+    }
+
+    let mut lines: Vec<String> = groups
+        .iter()
+        .take(DROPPED_EVENTS_LIMIT)
+        .map(|(summary, rule, count)| match format {
+            MessageFormat::Html => format!(
+                "<li>{} ({count}) -- {}</li>",
+                escape_html(summary),
+                escape_html(rule)
+            ),
+            MessageFormat::Markdown => format!(
+                "- {} ({count}) -- {}",
+                escape_markdown(summary),
+                escape_markdown(rule)
+            ),
+            MessageFormat::AsciiTable | MessageFormat::Plain => {
+                format!("- {summary} ({count}) -- {rule}")
+            }
+        })
+        .collect();
+    if groups.len() > DROPPED_EVENTS_LIMIT {
+        let more = format!("... and {} more", groups.len() - DROPPED_EVENTS_LIMIT);
+        lines.push(match format {
+            MessageFormat::Html => format!("<li>{more}</li>"),
+            _ => more,
+        });
+    }
+
+    let body = match format {
+        MessageFormat::Html => format!("<ul>\n{}\n</ul>\n", lines.join("\n")),
+        MessageFormat::AsciiTable | MessageFormat::Markdown | MessageFormat::Plain => {
+            format!("{}\n", lines.join("\n"))
+        }
+    };
+
+    Notification {
+        body,
+        calendar_name: Some(report.calendar_name.clone()),
+        change_type: None,
+        context: Value::Null,
+        events: Vec::new(),
+        title: format!(
+            "'{}' -- {} events filtered out",
+            report.calendar_name,
+            report.dropped.len()
+        ),
+    }
+}
+
+fn build_event_messages(
+    diffs: &[CalendarDiff],
+    reports: Vec<DiffReport>,
+) -> Result<Vec<Notification>> {
+    let mut messages = Vec::with_capacity(reports.len());
+
+    // Reports contain the rendered events in the same order as the diffs
+    for (diff, report) in diffs.iter().zip(reports) {
+        let calendar_name = &report.calendar_name;
+
+        let title = format!("'{calendar_name}' -- Event deleted");
+        for (event, msg) in diff.deletions.iter().zip(report.deletions) {
+            let data = EventData::from(event);
+            messages.push(Notification {
+                body: msg,
+                calendar_name: Some(calendar_name.clone()),
+                change_type: Some(ChangeType::Deletion),
+                context: json!({
+                    "calendar": calendar_name,
+                    "change": "deleted",
+                    "changes": [],
+                    "event": data,
+                    "new": null,
+                    "old": data,
+                }),
+                events: vec![(ChangeType::Deletion, event.clone())],
+                title: title.clone(),
+            });
+        }
+
+        let title = format!("'{calendar_name}' -- Event added");
+        for (event, msg) in diff.insertions.iter().zip(report.insertions) {
+            let data = EventData::from(event);
+            messages.push(Notification {
+                body: msg,
+                calendar_name: Some(calendar_name.clone()),
+                change_type: Some(ChangeType::Insertion),
+                context: json!({
+                    "calendar": calendar_name,
+                    "change": "added",
+                    "changes": [],
+                    "event": data,
+                    "new": data,
+                    "old": null,
+                }),
+                events: vec![(ChangeType::Insertion, event.clone())],
+                title: title.clone(),
+            });
+        }
+
+        let title = format!("'{calendar_name}' -- Event modified");
+        for ((old, new), msg) in diff.modifications.iter().zip(report.modifications) {
+            let changes = field_changes(old, new)?;
+            let data = EventData::from(new);
+            messages.push(Notification {
+                body: msg,
+                calendar_name: Some(calendar_name.clone()),
+                change_type: Some(ChangeType::Modification),
+                context: json!({
+                    "calendar": calendar_name,
+                    "change": "modified",
+                    "changes": changes,
+                    "event": data,
+                    "new": data,
+                    "old": EventData::from(old),
+                }),
+                events: vec![(ChangeType::Modification, new.clone())],
+                title: title.clone(),
+            });
+        }
+    }
+
+    Ok(messages)
+}
+
+fn build_import_message(summary: &ImportSummary, format: MessageFormat) -> Notification {
+    let total = summary.kept + summary.dropped;
+    let mut lines = vec![format!(
+        "{total} events, {} kept, {} dropped",
+        summary.kept, summary.dropped
+    )];
+    if total > 0 && summary.kept == 0 {
+        lines.push("No events were kept, check the filter rules!".to_string());
+    }
+
+    let dropped: Vec<String> = summary
+        .top_dropped
+        .iter()
+        .map(|(event_summary, count)| match format {
+            MessageFormat::Html => format!("<li>{} ({count})</li>", escape_html(event_summary)),
+            MessageFormat::Markdown => format!("- {} ({count})", escape_markdown(event_summary)),
+            MessageFormat::AsciiTable | MessageFormat::Plain => {
+                format!("- {event_summary} ({count})")
+            }
+        })
+        .collect();
+
+    let body = match format {
+        MessageFormat::Html => {
+            let mut body = format!("<p>{}</p>\n", lines.join("<br>\n"));
+            if !dropped.is_empty() {
+                body.push_str(&format!(
+                    "<p>Most dropped:</p>\n<ul>\n{}\n</ul>\n",
+                    dropped.join("\n")
+                ));
+            }
+            body
+        }
+        MessageFormat::AsciiTable | MessageFormat::Markdown | MessageFormat::Plain => {
+            let mut body = format!("{}\n", lines.join("\n"));
+            if !dropped.is_empty() {
+                body.push_str(&format!("\nMost dropped:\n{}\n", dropped.join("\n")));
+            }
+            body
+        }
+    };
+
+    Notification {
+        body,
+        calendar_name: Some(summary.calendar_name.clone()),
+        change_type: None,
+        context: Value::Null,
+        events: Vec::new(),
+        title: format!("'{}' -- Initial import", summary.calendar_name),
+    }
+}
+
+/// Calendars with the changed events, deletions are sent as cancellations
+fn build_invitations(
+    events: &[(ChangeType, Event)],
+    method: InvitationMethod,
+    organizer: &Mailbox,
+    attendees: &Mailboxes,
+) -> Vec<Invitation> {
+    let method = match method {
+        InvitationMethod::Publish => "PUBLISH",
+        InvitationMethod::Request => "REQUEST",
+    };
+
+    let mut cancelled = Calendar::new();
+    let mut updated = Calendar::new();
+
+    for (change_type, event) in events {
+        if *change_type == ChangeType::Deletion {
+            let mut event = scheduled_event(event, organizer, attendees);
+            let sequence = event.get_sequence().unwrap_or_default() + 1;
+            cancelled.push(
+                event
+                    .status(EventStatus::Cancelled)
+                    .sequence(sequence)
+                    .done(),
+            );
+        } else if method == "REQUEST" {
+            updated.push(scheduled_event(event, organizer, attendees));
+        } else {
+            updated.push(event.clone());
+        }
+    }
+
+    let mut invitations = Vec::with_capacity(2);
+    for (calendar, method, filename) in [
+        (updated, method, "invite.ics"),
+        (cancelled, "CANCEL", "cancel.ics"),
+    ] {
+        if calendar.components.is_empty() {
+            continue;
+        }
+
+        let mut calendar = calendar;
+        calendar.append_property(("METHOD", method));
+        invitations.push(Invitation {
+            filename: filename.to_string(),
+            ics: calendar.done().to_string(),
+            method: method.to_string(),
+        });
+    }
+
+    invitations
+}
+
+fn build_messages(
+    diffs: &[CalendarDiff],
+    format: MessageFormat,
+    digest: DigestMode,
+    templates: &TemplateConfig,
+) -> Result<Vec<Notification>> {
+    let mut reports = Vec::with_capacity(diffs.len());
+    for diff in diffs {
+        let report = generate_diff_report(diff, format)
+            .with_context(|| format!("Failed to generate report for '{}'", diff.calendar_name))?;
+        reports.push(report);
+    }
+
+    let mut messages = Vec::with_capacity(reports.len());
+    match digest {
+        DigestMode::Calendar => {
+            for (diff, report) in diffs.chunks(1).zip(reports.chunks(1)) {
+                messages.extend(build_digest(diff, report, format)?);
+            }
+        }
+        DigestMode::Disabled => messages = build_event_messages(diffs, reports)?,
+        DigestMode::Run => messages.extend(build_digest(diffs, &reports, format)?),
+    };
+
+    apply_templates(&mut messages, templates, format)?;
+
+    Ok(messages)
+}
+
+fn build_reminder_message(reminder: &Reminder, format: MessageFormat) -> Notification {
+    let (title, time) = if reminder.all_day {
+        (
+            format!(
+                "'{}' -- {} on {}",
+                reminder.calendar_name,
+                reminder.summary,
+                reminder.start.format("%A, %Y-%m-%d")
+            ),
+            "all day".to_string(),
+        )
+    } else {
+        (
+            format!(
+                "'{}' -- {} at {}",
+                reminder.calendar_name,
+                reminder.summary,
+                reminder.start.format("%H:%M")
+            ),
+            format!(
+                "{} - {}",
+                reminder.start.format("%A, %Y-%m-%d %H:%M"),
+                reminder.end.format("%H:%M")
+            ),
+        )
+    };
+    let location = reminder.location.as_deref().unwrap_or_default();
+
+    let body = match format {
+        MessageFormat::Html => format!(
+            "<p><b>{}</b><br>\n{time}<br>\n{}</p>\n",
+            escape_html(&reminder.summary),
+            escape_html(location)
+        ),
+        MessageFormat::Markdown => format!(
+            "**{}**\n\n{time}\n\n{}\n",
+            escape_markdown(&reminder.summary),
+            escape_markdown(location)
+        ),
+        MessageFormat::AsciiTable | MessageFormat::Plain => {
+            format!("{}\n{time}\n{location}\n", reminder.summary)
+        }
+    };
+
+    Notification {
+        body,
+        calendar_name: Some(reminder.calendar_name.clone()),
+        change_type: None,
+        context: Value::Null,
+        events: Vec::new(),
+        title,
+    }
+}
+
+fn build_rule_message(stale_rules: &StaleRules, format: MessageFormat) -> Notification {
+    let lines: Vec<String> = stale_rules
+        .rules
+        .iter()
+        .map(|stale_rule| {
+            let reason = match stale_rule.last_match {
+                None if stale_rule.whitelist => "never matched, check for typos".to_string(),
+                None => format!("never matched in {} runs", stale_rule.unmatched_runs),
+                Some(last_match) => format!(
+                    "no match since {} ({} runs)",
+                    last_match.format("%Y-%m-%d"),
+                    stale_rule.unmatched_runs
+                ),
+            };
+            match format {
+                MessageFormat::Html => {
+                    format!("<li>{} -- {reason}</li>", escape_html(&stale_rule.rule))
+                }
+                MessageFormat::Markdown => {
+                    format!("- {} -- {reason}", escape_markdown(&stale_rule.rule))
+                }
+                MessageFormat::AsciiTable | MessageFormat::Plain => {
+                    format!("- {} -- {reason}", stale_rule.rule)
+                }
+            }
+        })
+        .collect();
+
+    let body = match format {
+        MessageFormat::Html => format!("<ul>\n{}\n</ul>\n", lines.join("\n")),
+        MessageFormat::AsciiTable | MessageFormat::Markdown | MessageFormat::Plain => {
+            format!("{}\n", lines.join("\n"))
+        }
+    };
+
+    Notification {
+        body,
+        calendar_name: Some(stale_rules.calendar_name.clone()),
+        change_type: None,
+        context: Value::Null,
+        events: Vec::new(),
+        title: format!(
+            "'{}' -- {} filter rules without matches",
+            stale_rules.calendar_name,
+            stale_rules.rules.len()
+        ),
+    }
+}
+
+fn build_webhook_body(config: &WebhookConfig, payload: &WebhookPayload) -> Result<String> {
+    let payload_json =
+        serde_json::to_string(payload).with_context(|| "Failed to serialize webhook payload")?;
+
+    // `payload`, `summary` and `timestamp` are JSON encoded, so templates can embed them anywhere
+    // in a JSON body. The unencoded changes are available as `calendars` for loops and conditions.
+    let body = match &config.template {
+        Some(template) => {
+            let context = json!({
+                "calendars": payload.calendars,
+                "payload": payload_json,
+                "run": run_metadata(),
+                "summary": serde_json::to_string(&payload.summary)?,
+                "timestamp": serde_json::to_string(&payload.timestamp)?,
+            });
+            render_template(
+                template,
+                &TemplateValue::from_serialize(&context),
+                MessageFormat::Plain,
+            )
+            .with_context(|| "Failed to render webhook template")?
+        }
+        None => payload_json,
+    };
+
+    Ok(body)
+}
+
+fn build_webhook_payload(diffs: &[CalendarDiff]) -> Result<Option<WebhookPayload>> {
+    let deletions: usize = diffs.iter().map(|diff| diff.deletions.len()).sum();
+    let insertions: usize = diffs.iter().map(|diff| diff.insertions.len()).sum();
+    let modifications: usize = diffs.iter().map(|diff| diff.modifications.len()).sum();
+
+    if deletions + insertions + modifications == 0 {
+        return Ok(None);
+    }
+
+    let mut calendars = Vec::with_capacity(diffs.len());
+    for diff in diffs {
+        calendars.push(calendar_changes(diff)?);
+    }
+
+    Ok(Some(WebhookPayload {
+        calendars,
+        summary: summarize_counts(deletions, insertions, modifications),
+        timestamp: Local::now().to_rfc3339(),
+    }))
+}
+
+/// Calendar user address property of a mailbox, e.g. an ORGANIZER or ATTENDEE
+fn calendar_address(name: &str, mailbox: &Mailbox) -> Property {
+    let mut property = Property::new(name, format!("mailto:{}", mailbox.email));
+    if let Some(common_name) = &mailbox.name {
+        property.add_parameter("CN", common_name);
+    }
+    property
+}
+
+/// Notification routing of all calendars that restrict it, by calendar name
+fn calendar_routes(config: &Config) -> Result<HashMap<String, NotificationRoutingConfig>> {
+    let mut routes = HashMap::new();
+
+    for calendar in &config.calendars {
+        if let Some(routing) = &calendar.notifications {
+            routes.insert(calendar_name(calendar)?, routing.clone());
+        }
+    }
+
+    Ok(routes)
+}
+
+fn change_color(change_type: Option<ChangeType>) -> u32 {
+    match change_type {
+        Some(ChangeType::Deletion) => 0xe74c3c,
+        Some(ChangeType::Insertion) => 0x2ecc71,
+        Some(ChangeType::Modification) => 0xf39c12,
+        None => 0x3498db,
+    }
+}
+
+/// Resolves the message format of a channel, falling back to the global notification format.
+fn channel_format(
+    config: &NotificationConfig,
+    channel_format: Option<MessageFormat>,
+) -> MessageFormat {
+    if let Some(format) = channel_format.or(config.format) {
+        return format;
+    }
+
+    if config.format_as_table == Some(true) {
+        MessageFormat::AsciiTable
+    } else {
+        MessageFormat::Plain
+    }
+}
+
+/// Sends a delivery, `id` stays the same when the delivery is retried
+fn deliver(config: &NotificationConfig, delivery: &Delivery, id: &str) -> Result<()> {
+    let channel = delivery.channel();
+    println!(
+        "[{}] Sending {channel} notifications.",
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    let not_configured = || anyhow!("{channel} notifications are no longer configured");
+
+    match delivery {
+        Delivery::Discord { messages } => {
+            let discord_config = config.discord.as_ref().ok_or_else(not_configured)?;
+            push_messages_discord(discord_config, messages)
+        }
+        Delivery::Email {
+            html_messages,
+            invitations,
+            plain_messages,
+            recipients,
+        } => {
+            let mut email_config = config.email.clone().ok_or_else(not_configured)?;
+            if let Some(recipients) = recipients {
+                email_config.recipients = recipients.clone();
+            }
+            push_messages_email(&email_config, plain_messages, html_messages, invitations)
+        }
+        Delivery::Gotify { format, messages } => {
+            let gotify_config = config.gotify.as_ref().ok_or_else(not_configured)?;
+            push_messages_gotify(gotify_config, messages, *format)
+        }
+        Delivery::Matrix {
+            html_messages,
+            plain_messages,
+            room_ids,
+        } => {
+            let mut matrix_config = config.matrix.clone().ok_or_else(not_configured)?;
+            if let Some(room_ids) = room_ids {
+                matrix_config.room_ids = room_ids.clone();
+            }
+            push_messages_matrix(&matrix_config, plain_messages, html_messages, id)
+        }
+        Delivery::Mattermost { messages } => {
+            let mattermost_config = config.mattermost.as_ref().ok_or_else(not_configured)?;
+            push_messages_mattermost(mattermost_config, messages)
+        }
+        Delivery::Ntfy { format, messages } => {
+            let ntfy_config = config.ntfy.as_ref().ok_or_else(not_configured)?;
+            push_messages_ntfy(ntfy_config, messages, *format)
+        }
+        Delivery::Slack { messages } => {
+            let slack_config = config.slack.as_ref().ok_or_else(not_configured)?;
+            push_messages_slack(slack_config, messages)
+        }
+        Delivery::Telegram { chat_ids, messages } => {
+            let mut telegram_config = config.telegram.clone().ok_or_else(not_configured)?;
+            if let Some(chat_ids) = chat_ids {
+                telegram_config.chat_ids = chat_ids.clone();
+            }
+            push_messages_telegram(&telegram_config, messages)
+        }
+        Delivery::Webhook { body } => {
+            let webhook_config = config.webhook.as_ref().ok_or_else(not_configured)?;
+            push_webhook(webhook_config, body)
+        }
+    }
+}
+
+fn digest_heading(text: &str, level: usize, format: MessageFormat) -> String {
+    match format {
+        MessageFormat::Html => format!("<h{level}>{}</h{level}>\n", escape_html(text)),
+        MessageFormat::Markdown => format!("{} {}\n\n", "#".repeat(level), escape_markdown(text)),
+        MessageFormat::AsciiTable | MessageFormat::Plain => {
+            let underline = if level == 1 { "=" } else { "-" };
+            format!("\n{text}\n{}\n", underline.repeat(text.chars().count()))
+        }
+    }
+}
+
+/// Wraps an HTML message in a document styled for mail clients
+fn email_html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>{EMAIL_HTML_STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape_html(title)
+    )
+}
+
+/// Sender of emails, which is also the organizer of invitations
+fn email_sender(config: &EmailConfig) -> Result<Mailbox> {
+    let from = config
+        .from
+        .as_ref()
+        .or(config.username.as_ref())
+        .context("Email notifications need a 'from' address or a username")?;
+    from.parse()
+        .with_context(|| "Failed to parse sender email address")
+}
+
+/// Escapes the characters with special meaning in Slack's mrkdwn and Telegram's HTML parse mode.
+fn escape_entities(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Sends all due deliveries of the outbox, failed ones are kept for the next run
+fn flush_outbox(config: &NotificationConfig) -> Result<()> {
+    let outbox: Vec<OutboxEntry<Delivery>> = load_outbox()?;
+    let remaining = process_outbox(outbox, config.outbox.as_ref(), |id, delivery| {
+        deliver(config, delivery, id)
+    });
+    save_outbox(&remaining)
+}
+
+/// Builds the message endpoint from the configured server, which may be a bare host name or a
+/// full base URL including scheme, port and sub-path.
+fn gotify_message_url(server: &str) -> Result<Url> {
+    let mut base = if server.contains("://") {
+        server.to_string()
+    } else {
+        format!("https://{server}")
+    };
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+
+    let base = Url::parse(&base).with_context(|| format!("Invalid gotify server '{server}'"))?;
+    base.join("message")
+        .with_context(|| format!("Invalid gotify server '{server}'"))
+}
+
+fn invitation_content_type(invitation: &Invitation) -> Result<ContentType> {
+    ContentType::parse(&format!(
+        "text/calendar; charset=utf-8; method={}",
+        invitation.method
+    ))
+    .with_context(|| "Failed to build invitation content type")
+}
+
+/// Deliveries of a single message to the given channels that are configured and the calendar
+/// is routed to. The message is rendered in the format of each channel.
+fn message_deliveries(
+    config: &NotificationConfig,
+    route: Option<&NotificationRoutingConfig>,
+    channels: &[Channel],
+    build: impl Fn(MessageFormat) -> Notification,
+) -> Vec<Delivery> {
+    let mut deliveries = Vec::new();
+
+    for channel in channels {
+        if route
+            .and_then(|route| route.channels.as_ref())
+            .is_some_and(|channels| !channels.contains(channel))
+        {
+            continue;
+        }
+
+        let delivery = match channel {
+            Channel::Discord if config.discord.is_some() => Delivery::Discord {
+                messages: vec![build(MessageFormat::Markdown)],
+            },
+            Channel::Email => match &config.email {
+                Some(email_config) => {
+                    let plain_format = match channel_format(config, email_config.format) {
+                        MessageFormat::Html => MessageFormat::Plain,
+                        format => format,
+                    };
+                    Delivery::Email {
+                        html_messages: vec![build(MessageFormat::Html)],
+                        invitations: Vec::new(),
+                        plain_messages: vec![build(plain_format)],
+                        recipients: route.and_then(|route| route.recipients.clone()),
+                    }
+                }
+                None => continue,
+            },
+            Channel::Gotify => match &config.gotify {
+                Some(gotify_config) => {
+                    let format = channel_format(config, gotify_config.format);
+                    Delivery::Gotify {
+                        format,
+                        messages: vec![build(format)],
+                    }
+                }
+                None => continue,
+            },
+            Channel::Matrix if config.matrix.is_some() => Delivery::Matrix {
+                html_messages: vec![build(MessageFormat::Html)],
+                plain_messages: vec![build(MessageFormat::Plain)],
+                room_ids: route.and_then(|route| route.room_ids.clone()),
+            },
+            Channel::Mattermost if config.mattermost.is_some() => Delivery::Mattermost {
+                messages: vec![build(MessageFormat::Markdown)],
+            },
+            Channel::Ntfy => match &config.ntfy {
+                Some(ntfy_config) => {
+                    let format = channel_format(config, ntfy_config.format);
+                    Delivery::Ntfy {
+                        format,
+                        messages: vec![build(format)],
+                    }
+                }
+                None => continue,
+            },
+            Channel::Slack if config.slack.is_some() => Delivery::Slack {
+                messages: vec![build(MessageFormat::Plain)],
+            },
+            Channel::Telegram if config.telegram.is_some() => Delivery::Telegram {
+                chat_ids: route.and_then(|route| route.chat_ids.clone()),
+                messages: vec![build(MessageFormat::Plain)],
+            },
+            _ => continue,
+        };
+        deliveries.push(delivery);
+    }
+
+    deliveries
+}
+
 fn notifications_configured(config: &Config) -> bool {
     if config.notifications.is_some() {
         return true;
     }
 
-    false
-}
+    false
+}
+
+fn parse_mailboxes(addresses: &[String]) -> Result<Mailboxes> {
+    Ok(addresses.join(",").parse()?)
+}
+
+fn post_json(client: &Client, url: &Url, payload: &Value) -> Result<()> {
+    client
+        .post(url.clone())
+        .json(payload)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| {
+            format!(
+                "Failed to post message to '{}'",
+                url.origin().ascii_serialization()
+            )
+        })?;
+
+    Ok(())
+}
+
+/// Sends agendas through the email and gotify channels the calendars are routed to
+pub fn push_agenda_notifications(config: &Config, agendas: &[Agenda]) -> Result<()> {
+    if !notifications_configured(config) {
+        return Ok(());
+    }
+
+    let notification_config = config.notifications.clone().unwrap();
+    let routes = calendar_routes(config)?;
+    let mut deliveries = Vec::new();
+
+    for agenda in agendas {
+        deliveries.extend(message_deliveries(
+            &notification_config,
+            routes.get(&agenda.calendar_name),
+            &[Channel::Email, Channel::Gotify],
+            |format| build_agenda_message(agenda, format),
+        ));
+    }
+
+    queue_deliveries(&notification_config, deliveries)?;
+    flush_outbox(&notification_config)
+}
+
+/// Sends an alert through the channels selected by the alerts config
+pub fn push_alert_notification(config: &Config, alert: &Alert) -> Result<()> {
+    let Some(notification_config) = config.notifications.clone() else {
+        return Ok(());
+    };
+    let Some(alerts_config) = &notification_config.alerts else {
+        return Ok(());
+    };
+
+    let deliveries = message_deliveries(
+        &notification_config,
+        Some(&alerts_config.route),
+        &MESSAGE_CHANNELS,
+        |format| build_alert_message(alert, format),
+    );
+
+    queue_deliveries(&notification_config, deliveries)?;
+    flush_outbox(&notification_config)
+}
+
+/// Sends the events removed from updated calendars if enabled, through the channels they are
+/// routed to
+pub fn push_dropped_notifications(config: &Config, reports: &[&DroppedReport]) -> Result<()> {
+    let Some(notification_config) = config.notifications.clone() else {
+        return Ok(());
+    };
+    if !notification_config.include_dropped.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let routes = calendar_routes(config)?;
+    let mut deliveries = Vec::new();
+
+    for report in reports.iter().filter(|report| !report.dropped.is_empty()) {
+        deliveries.extend(message_deliveries(
+            &notification_config,
+            routes.get(&report.calendar_name),
+            &MESSAGE_CHANNELS,
+            |format| build_dropped_message(report, format),
+        ));
+    }
+
+    queue_deliveries(&notification_config, deliveries)?;
+    flush_outbox(&notification_config)
+}
+
+/// Sends the summaries of newly added calendars through the channels they are routed to
+pub fn push_import_notifications(config: &Config, imports: &[ImportSummary]) -> Result<()> {
+    if !notifications_configured(config) || imports.is_empty() {
+        return Ok(());
+    }
+
+    let notification_config = config.notifications.clone().unwrap();
+    let routes = calendar_routes(config)?;
+    let mut deliveries = Vec::new();
+
+    for summary in imports {
+        deliveries.extend(message_deliveries(
+            &notification_config,
+            routes.get(&summary.calendar_name),
+            &MESSAGE_CHANNELS,
+            |format| build_import_message(summary, format),
+        ));
+    }
 
-fn push_messages_email(config: &EmailConfig, messages: &Vec<(String, String)>) -> Result<()> {
-    let recipients = &config.recipients.join(",");
-    let from_mailbox: Mailbox = config
-        .username
-        .parse()
-        .with_context(|| "Failed to parse sender email address")?;
-    let to_mailboxes: Mailboxes = recipients
-        .parse()
-        .with_context(|| "Failed to parse recipient email addresses")?;
-    let to_header: lettre::message::header::To = to_mailboxes.into();
+    queue_deliveries(&notification_config, deliveries)?;
+    flush_outbox(&notification_config)
+}
 
-    let credentials = Credentials::new(config.username.clone(), config.password.clone());
-    let mailer = SmtpTransport::relay(&config.smtp_server)?
-        .credentials(credentials)
-        .build();
+fn push_messages_discord(
+    config: &IncomingWebhookConfig,
+    messages: &Vec<Notification>,
+) -> Result<()> {
+    let mut embeds = Vec::new();
 
     for msg in messages {
-        let subject = msg.0.clone();
-        let body = msg.1.clone();
+        let title = truncate(&msg.title, DISCORD_EMBED_TITLE_LIMIT);
+        for description in split_text(&msg.body, DISCORD_EMBED_DESCRIPTION_LIMIT) {
+            embeds.push((title.clone(), description, change_color(msg.change_type)));
+        }
+    }
+
+    let client = Client::new();
+    let mut batch: Vec<Value> = Vec::new();
+    let mut batch_len = 0;
+
+    for (title, description, color) in embeds {
+        let embed_len = title.chars().count() + description.chars().count();
+        if batch.len() == DISCORD_EMBEDS_PER_MESSAGE
+            || batch_len + embed_len > DISCORD_MESSAGE_LIMIT
+        {
+            post_json(&client, &config.url, &json!({ "embeds": batch }))?;
+            batch.clear();
+            batch_len = 0;
+        }
+
+        batch.push(json!({ "title": title, "description": description, "color": color }));
+        batch_len += embed_len;
+    }
+
+    if !batch.is_empty() {
+        post_json(&client, &config.url, &json!({ "embeds": batch }))?;
+    }
+
+    Ok(())
+}
+
+fn push_messages_email(
+    config: &EmailConfig,
+    plain_messages: &[Notification],
+    html_messages: &[Notification],
+    invitations: &[Vec<Invitation>],
+) -> Result<()> {
+    let from_mailbox = email_sender(config)?;
+    let to_header: To = parse_mailboxes(&config.recipients)
+        .with_context(|| "Failed to parse recipient email addresses")?
+        .into();
+    let cc_header: Option<Cc> = match &config.cc {
+        Some(cc) => Some(
+            parse_mailboxes(cc)
+                .with_context(|| "Failed to parse CC email addresses")?
+                .into(),
+        ),
+        None => None,
+    };
+    let bcc_header: Option<Bcc> = match &config.bcc {
+        Some(bcc) => Some(
+            parse_mailboxes(bcc)
+                .with_context(|| "Failed to parse BCC email addresses")?
+                .into(),
+        ),
+        None => None,
+    };
+    let reply_to: Option<Mailbox> = match &config.reply_to {
+        Some(reply_to) => Some(
+            reply_to
+                .parse()
+                .with_context(|| "Failed to parse reply-to email address")?,
+        ),
+        None => None,
+    };
 
-        let email = Message::builder()
+    let mailer = smtp_transport(config)?;
+
+    for (idx, (plain, html)) in plain_messages.iter().zip(html_messages).enumerate() {
+        let mut body = MultiPart::alternative()
+            .singlepart(SinglePart::plain(plain.body.clone()))
+            .singlepart(SinglePart::html(email_html_document(
+                &html.title,
+                &html.body,
+            )));
+
+        // Mail clients only process the calendar part of the alternative, which can hold a
+        // single method. Every calendar is attached as well, so cancellations next to updates
+        // can still be imported.
+        let attachments = invitations.get(idx).map(Vec::as_slice).unwrap_or_default();
+        if let Some(invitation) = attachments.first() {
+            body = body.singlepart(
+                SinglePart::builder()
+                    .header(invitation_content_type(invitation)?)
+                    .body(invitation.ics.clone()),
+            );
+        }
+        if !attachments.is_empty() {
+            let mut mixed = MultiPart::mixed().multipart(body);
+            for invitation in attachments {
+                mixed = mixed.singlepart(
+                    Attachment::new(invitation.filename.clone())
+                        .body(invitation.ics.clone(), invitation_content_type(invitation)?),
+                );
+            }
+            body = mixed;
+        }
+
+        let mut builder = Message::builder()
             .mailbox(to_header.clone())
             .from(from_mailbox.clone())
-            .subject(subject)
-            .header(ContentType::TEXT_PLAIN)
-            .body(body)
+            .subject(plain.title.clone());
+        if let Some(cc_header) = &cc_header {
+            builder = builder.mailbox(cc_header.clone());
+        }
+        if let Some(bcc_header) = &bcc_header {
+            builder = builder.mailbox(bcc_header.clone());
+        }
+        if let Some(reply_to) = &reply_to {
+            builder = builder.reply_to(reply_to.clone());
+        }
+
+        let email = builder
+            .multipart(body)
             .with_context(|| "Failed to construct email")?;
 
         mailer
@@ -52,69 +1602,807 @@ fn push_messages_email(config: &EmailConfig, messages: &Vec<(String, String)>) -
     Ok(())
 }
 
-fn push_messages_gotify(config: &GotifyConfig, messages: &Vec<(String, String)>) -> Result<()> {
-    let mut url = Url::parse("https://gotify.net")?;
-    url.set_host(Some(&config.server))
-        .with_context(|| "Failed to insert configured domain")?;
-    url.set_path("message");
-    url.set_query(Some(&format!("token={}", &config.token)));
-    let url_str = url.to_string();
+fn push_messages_gotify(
+    config: &GotifyConfig,
+    messages: &Vec<Notification>,
+    format: MessageFormat,
+) -> Result<()> {
+    let url = gotify_message_url(&config.server)?;
+    let extras = if format == MessageFormat::Markdown {
+        Some(json!({ "client::display": { "contentType": "text/markdown" } }))
+    } else {
+        None
+    };
 
     let client = Client::new();
     for msg in messages {
-        let params = [("title", msg.0.clone()), ("message", msg.1.clone())];
-        let _ = client.post(&url_str).form(&params).send()?;
+        let gotify_message = GotifyMessage {
+            extras: extras.as_ref(),
+            message: &msg.body,
+            priority: config.priority,
+            title: &msg.title,
+        };
+
+        client
+            .post(url.clone())
+            .header("X-Gotify-Key", &config.token)
+            .json(&gotify_message)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to send gotify message to '{url}'"))?;
     }
 
     Ok(())
 }
 
-pub fn push_notifications(
-    config: &Config,
-    names: &[String],
-    reports: Vec<DiffReport>,
+fn push_messages_mattermost(
+    config: &IncomingWebhookConfig,
+    messages: &Vec<Notification>,
+) -> Result<()> {
+    let client = Client::new();
+
+    for msg in messages {
+        let text = format!("#### {}\n\n{}", escape_markdown(&msg.title), msg.body);
+        for chunk in split_text(&text, MATTERMOST_MESSAGE_LIMIT) {
+            post_json(&client, &config.url, &json!({ "text": chunk }))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends every message as plain text `body` with an HTML `formatted_body`, so `plain_messages`
+/// and `html_messages` have to be built from the same diffs with the same digest mode.
+fn push_messages_matrix(
+    config: &MatrixConfig,
+    plain_messages: &[Notification],
+    html_messages: &[Notification],
+    delivery_id: &str,
+) -> Result<()> {
+    let client = Client::new();
+
+    for (room_idx, room_id) in config.room_ids.iter().enumerate() {
+        for (idx, (plain, html)) in plain_messages.iter().zip(html_messages).enumerate() {
+            // Derived from the delivery, so the homeserver ignores retries of sent messages
+            let transaction_id = format!("ics-filter-{delivery_id}-{room_idx}-{idx}");
+
+            let mut url = config.homeserver.clone();
+            url.path_segments_mut()
+                .map_err(|_| anyhow!("Invalid matrix homeserver URL '{}'", config.homeserver))?
+                .pop_if_empty()
+                .extend([
+                    "_matrix",
+                    "client",
+                    "v3",
+                    "rooms",
+                    room_id,
+                    "send",
+                    "m.room.message",
+                    &transaction_id,
+                ]);
+
+            let body = format!("{}\n\n{}", plain.title, plain.body);
+            let formatted_body = format!("<h4>{}</h4>\n{}", escape_html(&html.title), html.body);
+            let matrix_message = MatrixMessage {
+                body: &body,
+                format: "org.matrix.custom.html",
+                formatted_body: &formatted_body,
+                msgtype: "m.text",
+            };
+
+            client
+                .put(url)
+                .bearer_auth(&config.access_token)
+                .json(&matrix_message)
+                .send()
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("Failed to send matrix message to room '{room_id}'"))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn push_messages_ntfy(
+    config: &NtfyConfig,
+    messages: &Vec<Notification>,
+    format: MessageFormat,
 ) -> Result<()> {
+    let client = Client::new();
+
+    for msg in messages {
+        // Points at the served calendar, or the serving directory for multi-calendar digests
+        let click = match (&config.click_url, &msg.calendar_name) {
+            (Some(base), Some(calendar_name)) => Some(
+                base.join(&format!("{calendar_name}.ics"))
+                    .with_context(|| "Failed to build ntfy click URL")?
+                    .to_string(),
+            ),
+            (Some(base), None) => Some(base.to_string()),
+            (None, _) => None,
+        };
+
+        let ntfy_message = NtfyMessage {
+            click,
+            markdown: format == MessageFormat::Markdown,
+            message: &msg.body,
+            priority: config.priority,
+            tags: config.tags.as_ref(),
+            title: &msg.title,
+            topic: &config.topic,
+        };
+
+        // JSON publishing allows non-ASCII titles, which are not allowed in HTTP headers
+        let mut request = client.post(config.server.clone()).json(&ntfy_message);
+        if let Some(token) = &config.token {
+            request = request.bearer_auth(token);
+        } else if let Some(username) = &config.username {
+            request = request.basic_auth(username, config.password.as_ref());
+        }
+
+        request
+            .send()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to publish to ntfy topic '{}'", config.topic))?;
+    }
+
+    Ok(())
+}
+
+fn push_messages_slack(config: &IncomingWebhookConfig, messages: &Vec<Notification>) -> Result<()> {
+    let client = Client::new();
+    let code_block_overhead = "```\n\n```".len();
+
+    for msg in messages {
+        let mut blocks = vec![json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": truncate(&msg.title, SLACK_HEADER_LIMIT) },
+        })];
+
+        // Code blocks keep the alignment of the plain text reports
+        for chunk in split_escaped(&msg.body, SLACK_SECTION_LIMIT - code_block_overhead) {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("```\n{chunk}\n```") },
+            }));
+        }
+
+        for batch in blocks.chunks(SLACK_BLOCKS_PER_MESSAGE) {
+            let payload = json!({ "text": msg.title, "blocks": batch });
+            post_json(&client, &config.url, &payload)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn push_messages_telegram(config: &TelegramConfig, messages: &Vec<Notification>) -> Result<()> {
+    let mut url = config
+        .api_url
+        .clone()
+        .unwrap_or(Url::parse(DEFAULT_TELEGRAM_API_URL)?);
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid telegram API URL"))?
+        .pop_if_empty()
+        .extend([&format!("bot{}", config.bot_token), "sendMessage"]);
+
+    let client = Client::new();
+
+    for msg in messages {
+        for text in telegram_texts(msg) {
+            for chat_id in &config.chat_ids {
+                let telegram_message = TelegramMessage {
+                    chat_id,
+                    disable_web_page_preview: true,
+                    parse_mode: "HTML",
+                    text: &text,
+                };
+
+                // Not using error_for_status() because its error message contains the bot token
+                let response = client
+                    .post(url.clone())
+                    .json(&telegram_message)
+                    .send()
+                    .map_err(|e| anyhow!("Failed to reach telegram API: {}", e.without_url()))?;
+
+                let status = response.status();
+                if !status.is_success() {
+                    let body = response.text().unwrap_or_default();
+                    bail!("Failed to send telegram message to chat '{chat_id}' ({status}): {body}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends reminders directly instead of through the outbox, because late reminders are useless.
+/// Returns for every reminder whether it was delivered through at least one channel.
+pub fn push_reminder_notifications(config: &Config, reminders: &[Reminder]) -> Result<Vec<bool>> {
+    if !notifications_configured(config) {
+        return Ok(vec![true; reminders.len()]);
+    }
+
+    let notification_config = config.notifications.clone().unwrap();
+    let routes = calendar_routes(config)?;
+    let mut delivered = Vec::with_capacity(reminders.len());
+
+    for reminder in reminders {
+        let deliveries = message_deliveries(
+            &notification_config,
+            routes.get(&reminder.calendar_name),
+            &MESSAGE_CHANNELS,
+            |format| build_reminder_message(reminder, format),
+        );
+
+        // Reminders without channels count as delivered, they would never be sent
+        let mut any_delivered = deliveries.is_empty();
+        for delivery in deliveries {
+            match deliver(&notification_config, &delivery, &new_entry_id()) {
+                Ok(()) => any_delivered = true,
+                Err(e) => println!(
+                    "[{}] Failed to send reminder: {e:#}",
+                    Local::now().format("%Y-%m-%dT%H:%M:%S")
+                ),
+            }
+        }
+        delivered.push(any_delivered);
+    }
+
+    Ok(delivered)
+}
+
+/// Sends the stale rules of the calendars through the channels they are routed to
+pub fn push_rule_notifications(config: &Config, warnings: &[StaleRules]) -> Result<()> {
     if !notifications_configured(config) {
         return Ok(());
     }
 
     let notification_config = config.notifications.clone().unwrap();
+    let routes = calendar_routes(config)?;
+    let mut deliveries = Vec::new();
 
-    let mut messages = Vec::with_capacity(reports.len());
+    for stale_rules in warnings {
+        deliveries.extend(message_deliveries(
+            &notification_config,
+            routes.get(&stale_rules.calendar_name),
+            &MESSAGE_CHANNELS,
+            |format| build_rule_message(stale_rules, format),
+        ));
+    }
 
-    for (calendar_name, report) in names.iter().zip(reports) {
-        let title = format!("'{calendar_name}' -- Event deleted");
+    queue_deliveries(&notification_config, deliveries)?;
+    flush_outbox(&notification_config)
+}
+
+pub fn push_notifications(config: &Config, diffs: &[CalendarDiff]) -> Result<()> {
+    if !notifications_configured(config) {
+        return Ok(());
+    }
+
+    let notification_config = config.notifications.clone().unwrap();
+
+    let mut schedule = load_schedule_state()?;
+    let deliveries = build_deliveries(config, &notification_config, diffs, &mut schedule)?;
+    // Saved before queueing, so released changes are not held back and sent again after a failure
+    save_schedule_state(&schedule)?;
+    queue_deliveries(&notification_config, deliveries)?;
+
+    flush_outbox(&notification_config)
+}
+
+/// Adds deliveries to the outbox, one entry per message and target. Persisted before sending, so
+/// no notification is lost if the run is interrupted.
+fn queue_deliveries(config: &NotificationConfig, deliveries: Vec<Delivery>) -> Result<()> {
+    let mut outbox: Vec<OutboxEntry<Delivery>> = load_outbox()?;
+    for delivery in deliveries {
+        outbox.extend(delivery.split(config).into_iter().map(OutboxEntry::new));
+    }
+    save_outbox(&outbox)
+}
+
+fn push_webhook(config: &WebhookConfig, body: &str) -> Result<()> {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    for (name, value) in config.headers.iter().flatten() {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid webhook header name '{name}'"))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for webhook header '{name}'"))?;
+        headers.insert(name, value);
+    }
+
+    if let Some(secret) = &config.secret {
+        let signature = webhook_signature(secret, body)?;
+
+        let header = config
+            .signature_header
+            .as_deref()
+            .unwrap_or(DEFAULT_SIGNATURE_HEADER);
+        let name = HeaderName::from_bytes(header.as_bytes())
+            .with_context(|| format!("Invalid webhook signature header '{header}'"))?;
+        headers.insert(name, HeaderValue::from_str(&format!("sha256={signature}"))?);
+    }
+
+    let client = Client::new();
+    let retries = config.retries.unwrap_or(DEFAULT_WEBHOOK_RETRIES);
+
+    send_with_retries(retries, || {
+        client
+            .post(config.url.clone())
+            .headers(headers.clone())
+            .body(body.to_string())
+            .send()
+    })
+    .with_context(|| format!("Failed to deliver webhook to '{}'", config.url))?;
+
+    Ok(())
+}
+
+/// Renders a user supplied template, values are HTML escaped when rendering HTML messages
+fn render_template(
+    template: &str,
+    context: &TemplateValue,
+    format: MessageFormat,
+) -> Result<String> {
+    let mut environment = Environment::new();
+    if format == MessageFormat::Html {
+        environment.set_auto_escape_callback(|_| AutoEscape::Html);
+    }
+    Ok(environment.render_str(template, context)?)
+}
+
+/// Exponential backoff between retries, capped so that a long outage doesn't stall the run
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(2u64.saturating_pow(attempt).min(MAX_RETRY_DELAY_SECONDS))
+}
 
-        for msg in report.deletions {
-            messages.push((title.clone(), msg));
+/// Groups the diffs of all calendars routed to `channel` by the recipients replacing the ones of
+/// the channel config, `None` keeps the configured recipients. Calendars without routing are sent
+/// to every channel.
+fn route_diffs(
+    routes: &HashMap<String, NotificationRoutingConfig>,
+    diffs: &[CalendarDiff],
+    channel: Channel,
+) -> RoutedDiffs {
+    let mut groups: RoutedDiffs = Vec::new();
+
+    for diff in diffs {
+        let route = routes.get(&diff.calendar_name);
+        if let Some(channels) = route.and_then(|route| route.channels.as_ref())
+            && !channels.contains(&channel)
+        {
+            continue;
         }
 
-        let title = format!("'{calendar_name}' -- Event added");
-        for msg in report.insertions {
-            messages.push((title.clone(), msg));
+        let recipients = route.and_then(|route| match channel {
+            Channel::Email => route.recipients.clone(),
+            Channel::Matrix => route.room_ids.clone(),
+            Channel::Telegram => route.chat_ids.clone(),
+            _ => None,
+        });
+
+        match groups.iter_mut().find(|(group, _)| *group == recipients) {
+            Some((_, group_diffs)) => group_diffs.push(diff.clone()),
+            None => groups.push((recipients, vec![diff.clone()])),
+        }
+    }
+
+    groups
+}
+
+fn run_metadata() -> Value {
+    json!({
+        "timestamp": Local::now().to_rfc3339(),
+        "version": env!("CARGO_PKG_VERSION"),
+    })
+}
+
+/// Copy of the event with the sender as organizer and the recipients as attendees, which requests
+/// and cancellations need to be processed by mail clients
+fn scheduled_event(event: &Event, organizer: &Mailbox, attendees: &Mailboxes) -> Event {
+    let mut scheduled = Event::new();
+    for property in event.properties().values() {
+        scheduled.append_property(property.clone());
+    }
+    for (name, properties) in event.multi_properties() {
+        if name == "ATTENDEE" {
+            continue;
+        }
+        for property in properties {
+            scheduled.append_multi_property(property.clone());
         }
+    }
+    for component in event.components() {
+        scheduled.append_component(component.clone());
+    }
 
-        let title = format!("'{calendar_name}' -- Event modified");
-        for msg in report.modifications {
-            messages.push((title.clone(), msg));
+    scheduled.append_property(calendar_address("ORGANIZER", organizer));
+    for attendee in attendees.iter() {
+        scheduled.append_multi_property(calendar_address("ATTENDEE", attendee));
+    }
+    scheduled
+}
+
+/// Sends a request until it succeeds with a 2xx status, waiting with exponential backoff
+/// (capped at a minute) between the attempts.
+fn send_with_retries(
+    retries: u32,
+    send: impl Fn() -> reqwest::Result<Response>,
+) -> Result<Response> {
+    let mut attempt = 0;
+
+    loop {
+        match send().and_then(|response| response.error_for_status()) {
+            Ok(response) => return Ok(response),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                println!(
+                    "[{}] Request failed, retrying ({attempt}/{retries}): {e}",
+                    Local::now().format("%Y-%m-%dT%H:%M:%S")
+                );
+                sleep(retry_delay(attempt));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Builds the SMTP transport with the configured encryption, port and credentials
+fn smtp_transport(config: &EmailConfig) -> Result<SmtpTransport> {
+    // Keeps supporting servers configured as "host:port"
+    let (host, server_port) = match config.smtp_server.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (config.smtp_server.as_str(), None),
+        },
+        None => (config.smtp_server.as_str(), None),
+    };
+
+    let mut builder = match config.tls.unwrap_or_default() {
+        SmtpTls::None => SmtpTransport::builder_dangerous(host),
+        SmtpTls::Starttls => SmtpTransport::starttls_relay(host)
+            .with_context(|| format!("Failed to set up STARTTLS for '{host}'"))?,
+        SmtpTls::Tls => SmtpTransport::relay(host)
+            .with_context(|| format!("Failed to set up TLS for '{host}'"))?,
+    };
+
+    if let Some(port) = config.port.or(server_port) {
+        builder = builder.port(port);
+    }
+
+    match (&config.username, &config.password) {
+        (Some(username), Some(password)) => {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        (Some(_), None) => bail!("Email notifications have a username but no password"),
+        (None, _) => (),
+    }
+
+    Ok(builder.build())
+}
+
+/// Splits the raw text so that every chunk stays within `limit` once escaped, escaping a
+/// split text instead could cut an entity in half
+fn split_escaped(text: &str, limit: usize) -> Vec<String> {
+    let width = |c| match c {
+        '&' => "&amp;".len(),
+        '<' | '>' => "&lt;".len(),
+        _ => 1,
+    };
+
+    split_text_by(text, limit, width)
+        .iter()
+        .map(|chunk| escape_entities(chunk))
+        .collect()
+}
+
+/// One list per room or chat the delivery goes to, `targets` replace the ones of the channel
+/// config. `None` if there are no targets because the channel is no longer configured.
+fn split_targets(
+    targets: Option<Vec<String>>,
+    configured: Option<&Vec<String>>,
+) -> Vec<Option<Vec<String>>> {
+    match targets.or_else(|| configured.cloned()) {
+        Some(targets) => targets
+            .into_iter()
+            .map(|target| Some(vec![target]))
+            .collect(),
+        None => vec![None],
+    }
+}
+
+/// Splits a text into chunks of at most `limit` characters, preferring line breaks as split points.
+fn split_text(text: &str, limit: usize) -> Vec<String> {
+    split_text_by(text, limit, |_| 1)
+}
+
+/// Splits at line boundaries into chunks of at most `limit`, measured by the summed `width` of
+/// their characters. Lines longer than the limit are split between characters.
+fn split_text_by(text: &str, limit: usize, width: impl Fn(char) -> usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_len: usize = line.chars().map(&width).sum();
+
+        if current_len + line_len > limit && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+
+        if line_len > limit {
+            for c in line.chars() {
+                let char_len = width(c);
+                if current_len + char_len > limit && !current.is_empty() {
+                    chunks.push(std::mem::take(&mut current));
+                    current_len = 0;
+                }
+                current.push(c);
+                current_len += char_len;
+            }
+            continue;
         }
+
+        current.push_str(line);
+        current_len += line_len;
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+fn summarize_counts(deletions: usize, insertions: usize, modifications: usize) -> String {
+    let counts = [
+        (deletions, "deleted"),
+        (insertions, "added"),
+        (modifications, "modified"),
+    ];
+
+    counts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, label)| format!("{count} {label}"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Formats a message as Telegram HTML, split so that every text stays within the limit
+fn telegram_texts(msg: &Notification) -> Vec<String> {
+    let title = escape_entities(&msg.title);
+    let markup_len = "<b></b>\n<pre></pre>".len() + title.chars().count();
+    let chunk_limit = TELEGRAM_MESSAGE_LIMIT.saturating_sub(markup_len).max(1);
+
+    split_escaped(&msg.body, chunk_limit)
+        .iter()
+        .map(|chunk| format!("<b>{title}</b>\n<pre>{chunk}</pre>"))
+        .collect()
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(limit - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Hex encoded HMAC-SHA256 of the webhook body
+fn webhook_signature(secret: &str, body: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .with_context(|| "Failed to initialize webhook signature")?;
+    mac.update(body.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as EventDuration, NaiveDate};
+    use icalendar::EventLike;
+
+    fn event(uid: &str, summary: &str) -> Event {
+        let start = NaiveDate::from_ymd_opt(2026, 11, 2)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        Event::new()
+            .uid(uid)
+            .summary(summary)
+            .starts(start)
+            .ends(start + EventDuration::hours(1))
+            .done()
+    }
+
+    fn diffs() -> Vec<CalendarDiff> {
+        ["Home", "Work"]
+            .iter()
+            .map(|name| CalendarDiff {
+                calendar_name: name.to_string(),
+                deletions: Vec::new(),
+                insertions: vec![event(&format!("{name}-a"), "Lecture")],
+                modifications: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn calendar_digest_sends_one_message_per_calendar() {
+        let messages = build_messages(
+            &diffs(),
+            MessageFormat::Plain,
+            DigestMode::Calendar,
+            &TemplateConfig::default(),
+        )
+        .unwrap();
+
+        let names: Vec<_> = messages
+            .iter()
+            .map(|message| message.calendar_name.as_deref())
+            .collect();
+        assert_eq!(names, [Some("Home"), Some("Work")]);
+        assert!(messages[0].title.starts_with("'Home' -- "));
+        assert!(!messages[0].body.contains("Work"));
+    }
+
+    #[test]
+    fn run_digest_combines_all_calendars() {
+        let messages = build_messages(
+            &diffs(),
+            MessageFormat::Plain,
+            DigestMode::Run,
+            &TemplateConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].calendar_name, None);
+        assert!(messages[0].title.starts_with("ICS Filter -- "));
+        assert!(messages[0].body.contains("Home"));
+        assert!(messages[0].body.contains("Work"));
+        assert_eq!(messages[0].events.len(), 2);
     }
 
-    if notification_config.email.is_some() {
-        println!(
-            "[{}] Sending email notifications.",
-            Local::now().format("%Y-%m-%dT%H:%M:%S")
+    #[test]
+    fn html_templates_escape_values_but_not_the_report() {
+        let context = context! {
+            report => TemplateValue::from_safe_string("<p>report</p>".to_string()),
+            summary => "<script>alert(1)</script>",
+        };
+
+        let html = render_template("{{ summary }}{{ report }}", &context, MessageFormat::Html);
+        let plain = render_template("{{ summary }}", &context, MessageFormat::Plain);
+
+        assert_eq!(
+            html.unwrap(),
+            "&lt;script&gt;alert(1)&lt;&#x2f;script&gt;<p>report</p>"
         );
-        push_messages_email(&notification_config.email.clone().unwrap(), &messages)?;
+        assert_eq!(plain.unwrap(), "<script>alert(1)</script>");
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_a_minute() {
+        let delays: Vec<_> = [1, 2, 5, 6, 40]
+            .into_iter()
+            .map(|attempt| retry_delay(attempt).as_secs())
+            .collect();
+
+        assert_eq!(delays, [2, 4, 32, 60, 60]);
     }
 
-    if notification_config.gotify.is_some() {
-        println!(
-            "[{}] Sending gotify notifications.",
-            Local::now().format("%Y-%m-%dT%H:%M:%S")
+    #[test]
+    fn webhook_signature_is_hex_hmac_sha256() {
+        let signature = webhook_signature("secret", r#"{"a":1}"#).unwrap();
+
+        assert_eq!(
+            signature,
+            "aa9e2e3575f5d7098b6caccd790888c36d5fdb63342a73bada2d6a51747a8494"
         );
-        push_messages_gotify(&notification_config.gotify.clone().unwrap(), &messages)?;
     }
 
-    Ok(())
+    #[test]
+    fn split_text_prefers_line_boundaries() {
+        let chunks = split_text("one\ntwo\nthree\n", 9);
+
+        assert_eq!(chunks, ["one\ntwo\n", "three\n"]);
+    }
+
+    #[test]
+    fn split_text_splits_overlong_lines() {
+        let chunks = split_text("short\nabcdefghij\nend", 4);
+
+        assert_eq!(chunks, ["shor", "t\n", "abcd", "efgh", "ij\n", "end"]);
+    }
+
+    #[test]
+    fn split_text_counts_characters_not_bytes() {
+        let chunks = split_text("äöü€\nß", 5);
+
+        assert_eq!(chunks, ["äöü€\n", "ß"]);
+    }
+
+    #[test]
+    fn split_escaped_stays_within_limit_after_escaping() {
+        let text = "Tom & Jerry <live>\n".repeat(50);
+
+        let chunks = split_escaped(&text, 100);
+
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 100));
+        assert!(chunks.iter().all(|chunk| !chunk.contains('<')));
+        assert_eq!(chunks.concat(), escape_entities(&text));
+    }
+
+    fn notification(title: &str, body: &str) -> Notification {
+        Notification {
+            body: body.to_string(),
+            calendar_name: None,
+            change_type: None,
+            context: Value::Null,
+            events: Vec::new(),
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn telegram_texts_stay_within_limit() {
+        let msg = notification("'Work' -- 1 added", &"<b>Tom & Jerry</b>\n".repeat(1000));
+
+        let texts = telegram_texts(&msg);
+
+        assert!(texts.len() > 1);
+        for text in &texts {
+            assert!(text.chars().count() <= TELEGRAM_MESSAGE_LIMIT);
+            assert!(text.starts_with("<b>'Work' -- 1 added</b>\n<pre>"));
+            assert!(text.ends_with("</pre>"));
+        }
+    }
+
+    #[test]
+    fn telegram_texts_escape_title_and_body() {
+        let msg = notification("'R&D' -- <1> added", "Tom & Jerry <live>\n");
+
+        let texts = telegram_texts(&msg);
+
+        assert_eq!(
+            texts,
+            ["<b>'R&amp;D' -- &lt;1&gt; added</b>\n<pre>Tom &amp; Jerry &lt;live&gt;\n</pre>"]
+        );
+    }
+
+    #[test]
+    fn gotify_url_keeps_scheme_and_port() {
+        let url = gotify_message_url("http://10.0.0.5:8080").unwrap();
+
+        assert_eq!(url.as_str(), "http://10.0.0.5:8080/message");
+    }
+
+    #[test]
+    fn gotify_url_keeps_sub_path() {
+        let url = gotify_message_url("https://example.org/gotify").unwrap();
+
+        assert_eq!(url.as_str(), "https://example.org/gotify/message");
+    }
+
+    #[test]
+    fn gotify_url_accepts_bare_host() {
+        let url = gotify_message_url("gotify.example.org").unwrap();
+
+        assert_eq!(url.as_str(), "https://gotify.example.org/message");
+    }
+
+    #[test]
+    fn gotify_url_never_contains_token() {
+        let url = gotify_message_url("https://example.org/?token=AbCdEf").unwrap();
+
+        assert_eq!(url.as_str(), "https://example.org/message");
+        assert!(!url.as_str().contains("AbCdEf"));
+    }
 }
@@ -0,0 +1,218 @@
+use crate::cache::{is_cached, load_from_cache, save_to_cache};
+use crate::config::OutboxConfig;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static DEFAULT_MAX_AGE_HOURS: u32 = 48;
+static DEFAULT_RETRY_INTERVAL_MINUTES: u32 = 5;
+static MAX_RETRY_INTERVAL_HOURS: i64 = 12;
+static OUTBOX_FILE: &str = "outbox.json";
+
+static ENTRY_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Delivery that is persisted until it succeeded or expired
+#[derive(Deserialize, Serialize)]
+pub struct OutboxEntry<T> {
+    pub attempts: u32,
+    pub created: DateTime<Local>,
+    pub delivery: T,
+    /// Identifies the delivery across retries, so receivers can deduplicate it
+    #[serde(default = "new_entry_id")]
+    pub id: String,
+    pub next_attempt: DateTime<Local>,
+}
+
+impl<T> OutboxEntry<T> {
+    pub fn new(delivery: T) -> Self {
+        let now = Local::now();
+        OutboxEntry {
+            attempts: 0,
+            created: now,
+            delivery,
+            id: new_entry_id(),
+            next_attempt: now,
+        }
+    }
+}
+
+/// Waiting time before the next attempt, doubling with every failed attempt
+fn backoff(config: Option<&OutboxConfig>, attempts: u32) -> Duration {
+    let interval = config
+        .and_then(|config| config.retry_interval_minutes)
+        .unwrap_or(DEFAULT_RETRY_INTERVAL_MINUTES);
+    let factor = 2_i64.saturating_pow(attempts.saturating_sub(1));
+
+    // Capped before building the duration, which panics when out of range
+    let minutes = i64::from(interval)
+        .saturating_mul(factor)
+        .min(MAX_RETRY_INTERVAL_HOURS * 60);
+
+    Duration::minutes(minutes)
+}
+
+/// Unique id of a new delivery
+pub fn new_entry_id() -> String {
+    format!(
+        "{}-{}",
+        Local::now().format("%Y%m%dT%H%M%S%f"),
+        ENTRY_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+pub fn load_outbox<T: DeserializeOwned>() -> Result<Vec<OutboxEntry<T>>> {
+    if !is_cached(OUTBOX_FILE) {
+        return Ok(Vec::new());
+    }
+
+    let data = load_from_cache(OUTBOX_FILE)?;
+    serde_json::from_str(&data).with_context(|| "Failed to parse notification outbox!")
+}
+
+/// Attempts every due delivery and returns the entries that have to be retried later. Failed
+/// deliveries are logged and rescheduled with backoff until they exceed the maximum age.
+pub fn process_outbox<T>(
+    entries: Vec<OutboxEntry<T>>,
+    config: Option<&OutboxConfig>,
+    mut deliver: impl FnMut(&str, &T) -> Result<()>,
+) -> Vec<OutboxEntry<T>> {
+    let max_age_hours = config
+        .and_then(|config| config.max_age_hours)
+        .unwrap_or(DEFAULT_MAX_AGE_HOURS);
+    let max_age = Duration::hours(i64::from(max_age_hours));
+    let mut remaining = Vec::new();
+
+    for mut entry in entries {
+        let now = Local::now();
+        if entry.next_attempt > now {
+            remaining.push(entry);
+            continue;
+        }
+
+        match deliver(&entry.id, &entry.delivery) {
+            Ok(()) => (),
+            Err(e) if now - entry.created >= max_age => {
+                println!(
+                    "[{}] Dropping notification after {} attempts: {e:#}",
+                    now.format("%Y-%m-%dT%H:%M:%S"),
+                    entry.attempts + 1
+                );
+            }
+            Err(e) => {
+                entry.attempts += 1;
+                entry.next_attempt = now + backoff(config, entry.attempts);
+                println!(
+                    "[{}] Failed to deliver notification, retrying at {}: {e:#}",
+                    now.format("%Y-%m-%dT%H:%M:%S"),
+                    entry.next_attempt.format("%Y-%m-%dT%H:%M:%S")
+                );
+                remaining.push(entry);
+            }
+        }
+    }
+
+    remaining
+}
+
+pub fn save_outbox<T: Serialize>(entries: &[OutboxEntry<T>]) -> Result<()> {
+    let data = serde_json::to_string_pretty(entries)
+        .with_context(|| "Failed to serialize notification outbox!")?;
+    save_to_cache(&data, OUTBOX_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn config(max_age_hours: u32) -> OutboxConfig {
+        OutboxConfig {
+            max_age_hours: Some(max_age_hours),
+            retry_interval_minutes: Some(5),
+        }
+    }
+
+    fn entry(age: Duration) -> OutboxEntry<&'static str> {
+        let mut entry = OutboxEntry::new("message");
+        entry.created -= age;
+        entry.next_attempt -= age;
+        entry
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_attempt() {
+        let config = config(48);
+
+        assert_eq!(backoff(Some(&config), 1), Duration::minutes(5));
+        assert_eq!(backoff(Some(&config), 2), Duration::minutes(10));
+        assert_eq!(backoff(Some(&config), 4), Duration::minutes(40));
+        assert_eq!(backoff(None, 1), Duration::minutes(5));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let config = config(48);
+
+        assert_eq!(backoff(Some(&config), 10), Duration::hours(12));
+        assert_eq!(backoff(Some(&config), u32::MAX), Duration::hours(12));
+    }
+
+    #[test]
+    fn delivered_entries_are_removed() {
+        let remaining = process_outbox(vec![entry(Duration::zero())], None, |_, _| Ok(()));
+
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn failed_entries_are_rescheduled() {
+        let config = config(48);
+        let remaining = process_outbox(vec![entry(Duration::hours(1))], Some(&config), |_, _| {
+            Err(anyhow!("unreachable"))
+        });
+
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].attempts, 1);
+        assert!(remaining[0].next_attempt > Local::now() + Duration::minutes(4));
+    }
+
+    #[test]
+    fn expired_entries_are_dropped() {
+        let config = config(48);
+        let remaining = process_outbox(vec![entry(Duration::hours(48))], Some(&config), |_, _| {
+            Err(anyhow!("unreachable"))
+        });
+
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn entries_are_not_attempted_before_their_time() {
+        let mut waiting = entry(Duration::zero());
+        waiting.next_attempt += Duration::hours(1);
+
+        let remaining = process_outbox(vec![waiting], None, |_, _| panic!("attempted"));
+
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].attempts, 0);
+    }
+
+    #[test]
+    fn retries_keep_the_entry_id() {
+        let first = entry(Duration::zero());
+        let id = first.id.clone();
+        let mut attempted = Vec::new();
+
+        let remaining = process_outbox(vec![first], None, |id, _| {
+            attempted.push(id.to_string());
+            Err(anyhow!("unreachable"))
+        });
+
+        assert_eq!(attempted, vec![id.clone()]);
+        assert_eq!(remaining[0].id, id);
+        assert_ne!(entry(Duration::zero()).id, id);
+    }
+}
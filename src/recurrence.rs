@@ -0,0 +1,706 @@
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Utc, Weekday,
+};
+use chrono_tz::Tz;
+use icalendar::{
+    Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime, Event, EventStatus,
+    Property,
+};
+use std::collections::HashSet;
+
+static MAX_PERIODS: u32 = 50_000;
+
+/// Single occurrence of a possibly recurring event
+pub struct Occurrence<'a> {
+    pub all_day: bool,
+    pub end: DateTime<Local>,
+    pub event: &'a Event,
+    pub start: DateTime<Local>,
+}
+
+/// Timezone the local times of an event are expressed in
+#[derive(Clone, Copy)]
+enum Anchor {
+    Date,
+    Floating,
+    Utc,
+    Zoned(Tz),
+}
+
+#[derive(PartialEq)]
+enum Frequency {
+    Daily,
+    Monthly,
+    Weekly,
+    Yearly,
+}
+
+/// Supported subset of an RFC 5545 recurrence rule
+struct Rule {
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month: Vec<u32>,
+    by_month_day: Vec<i32>,
+    by_set_pos: Vec<i32>,
+    count: Option<u32>,
+    frequency: Frequency,
+    interval: u32,
+    until: Option<DateTime<Local>>,
+}
+
+impl Anchor {
+    fn to_local(self, date_time: NaiveDateTime) -> Option<DateTime<Local>> {
+        match self {
+            Anchor::Date | Anchor::Floating => Local.from_local_datetime(&date_time).earliest(),
+            Anchor::Utc => Some(Utc.from_utc_datetime(&date_time).with_timezone(&Local)),
+            Anchor::Zoned(tz) => tz
+                .from_local_datetime(&date_time)
+                .earliest()
+                .map(|date_time| date_time.with_timezone(&Local)),
+        }
+    }
+}
+
+/// Splits a start or end date into its local date time and the timezone it is expressed in
+fn anchor_of(date: &DatePerhapsTime) -> (NaiveDateTime, Anchor) {
+    match date {
+        DatePerhapsTime::Date(date) => (date.and_time(NaiveTime::MIN), Anchor::Date),
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => {
+            (*date_time, Anchor::Floating)
+        }
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => {
+            (date_time.naive_utc(), Anchor::Utc)
+        }
+        // Unknown timezones, e.g. Windows names, are interpreted as local time
+        DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => {
+            match tzid.parse::<Tz>() {
+                Ok(tz) => (*date_time, Anchor::Zoned(tz)),
+                Err(_) => (*date_time, Anchor::Floating),
+            }
+        }
+    }
+}
+
+/// Candidate dates of the `period`-th recurrence period, in chronological order. `None` if the
+/// period is beyond the supported date range.
+fn candidate_dates(rule: &Rule, start: NaiveDate, period: u32) -> Option<Vec<NaiveDate>> {
+    let steps = period.checked_mul(rule.interval)?;
+
+    let mut dates = match rule.frequency {
+        Frequency::Daily => {
+            let date = start.checked_add_signed(Duration::try_days(i64::from(steps))?)?;
+            if rule.by_day.is_empty() || rule.by_day.iter().any(|(_, day)| *day == date.weekday()) {
+                vec![date]
+            } else {
+                Vec::new()
+            }
+        }
+        Frequency::Weekly => {
+            let week_start =
+                start - Duration::days(i64::from(start.weekday().num_days_from_monday()));
+            let week_start =
+                week_start.checked_add_signed(Duration::try_weeks(i64::from(steps))?)?;
+            let weekdays: Vec<Weekday> = if rule.by_day.is_empty() {
+                vec![start.weekday()]
+            } else {
+                rule.by_day.iter().map(|(_, day)| *day).collect()
+            };
+            weekdays
+                .iter()
+                .map(|day| week_start + Duration::days(i64::from(day.num_days_from_monday())))
+                .collect()
+        }
+        Frequency::Monthly => {
+            let month = start.with_day(1)?.checked_add_months(Months::new(steps))?;
+            month_dates(rule, month, start.day())
+        }
+        Frequency::Yearly => {
+            let months = if rule.by_month.is_empty() {
+                vec![start.month()]
+            } else {
+                rule.by_month.clone()
+            };
+            let year = start.year().checked_add(i32::try_from(steps).ok()?)?;
+            // Years beyond the supported range end the expansion
+            NaiveDate::from_ymd_opt(year, 1, 1)?;
+            months
+                .into_iter()
+                .filter_map(|month| NaiveDate::from_ymd_opt(year, month, 1))
+                .flat_map(|month| month_dates(rule, month, start.day()))
+                .collect()
+        }
+    };
+
+    if !rule.by_month.is_empty() && rule.frequency != Frequency::Yearly {
+        dates.retain(|date| rule.by_month.contains(&date.month()));
+    }
+    dates.sort();
+    dates.dedup();
+
+    // Picks from the dates of the period, negative positions count from its end
+    if !rule.by_set_pos.is_empty() {
+        let len = dates.len() as i32;
+        let mut selected: Vec<NaiveDate> = rule
+            .by_set_pos
+            .iter()
+            .map(|pos| if *pos < 0 { len + pos } else { pos - 1 })
+            .filter_map(|idx| dates.get(usize::try_from(idx).ok()?).copied())
+            .collect();
+        selected.sort();
+        selected.dedup();
+        dates = selected;
+    }
+
+    Some(dates)
+}
+
+/// Instants of all RDATE or EXDATE values of an event
+fn date_list(event: &Event, name: &str, anchor: Anchor) -> Vec<DateTime<Local>> {
+    let Some(properties) = event.multi_properties().get(name) else {
+        return Vec::new();
+    };
+
+    let mut instants = Vec::new();
+    for property in properties {
+        for value in property.value().split(',') {
+            let mut single = Property::new(name, value);
+            for (key, parameter) in property.params() {
+                single.add_parameter(key, parameter.value());
+            }
+
+            if let Some(date) = DatePerhapsTime::from_property(&single) {
+                let (date_time, value_anchor) = anchor_of(&date);
+                let value_anchor = match value_anchor {
+                    Anchor::Date | Anchor::Floating => anchor,
+                    value_anchor => value_anchor,
+                };
+                instants.extend(value_anchor.to_local(date_time));
+            }
+        }
+    }
+
+    instants
+}
+
+/// Occurrences of a single event starting inside the range, at most `limit` of them. Without
+/// `until` a recurring event is expanded until `limit` or the end of its rule is reached.
+fn event_occurrences<'a>(
+    event: &'a Event,
+    overridden: &HashSet<(String, DateTime<Local>)>,
+    from: DateTime<Local>,
+    until: Option<DateTime<Local>>,
+    limit: Option<usize>,
+) -> Vec<Occurrence<'a>> {
+    let mut result = Vec::new();
+    if event.get_status() == Some(EventStatus::Cancelled) {
+        return result;
+    }
+    let Some(start) = event.get_start() else {
+        return result;
+    };
+
+    let (start, anchor) = anchor_of(&start);
+    let duration = event_duration(event, start, anchor);
+    let uid = event.get_uid().unwrap_or_default();
+
+    let mut push = |instant: DateTime<Local>| {
+        if instant >= from
+            && until.is_none_or(|until| instant < until)
+            && !overridden.contains(&(uid.to_string(), instant))
+        {
+            result.push(Occurrence {
+                all_day: matches!(anchor, Anchor::Date),
+                end: instant + duration,
+                event,
+                start: instant,
+            });
+        }
+        result.len()
+    };
+
+    let rule = match event.property_value("RRULE") {
+        Some(rule) if !event.properties().contains_key("RECURRENCE-ID") => parse_rule(rule, anchor),
+        _ => None,
+    };
+
+    let Some(rule) = rule else {
+        if let Some(instant) = anchor.to_local(start) {
+            push(instant);
+        }
+        return result;
+    };
+
+    let excluded = date_list(event, "EXDATE", anchor);
+    let first = first_period(&rule, start.date(), from.date_naive());
+    let mut count = 0;
+
+    'periods: for period in first..first.saturating_add(MAX_PERIODS) {
+        let Some(dates) = candidate_dates(&rule, start.date(), period) else {
+            break;
+        };
+        for date in dates {
+            let date_time = date.and_time(start.time());
+            if date_time < start {
+                continue;
+            }
+            let Some(instant) = anchor.to_local(date_time) else {
+                continue;
+            };
+            if rule.count.is_some_and(|max| count >= max)
+                || rule.until.is_some_and(|rule_until| instant > rule_until)
+                || until.is_some_and(|until| instant >= until)
+            {
+                break 'periods;
+            }
+
+            count += 1;
+            if !excluded.contains(&instant) {
+                let found = push(instant);
+                if limit.is_some_and(|limit| found >= limit) {
+                    break 'periods;
+                }
+            }
+        }
+    }
+
+    for instant in date_list(event, "RDATE", anchor) {
+        push(instant);
+    }
+
+    result
+}
+
+/// Duration of every occurrence, all-day events without end last one day
+fn event_duration(event: &Event, start: NaiveDateTime, anchor: Anchor) -> Duration {
+    match event.get_end() {
+        Some(end) => anchor_of(&end).0 - start,
+        None if matches!(anchor, Anchor::Date) => Duration::days(1),
+        None => Duration::zero(),
+    }
+}
+
+/// First period that can contain occurrences after `from`, so long running series are not
+/// expanded from their start. Rules with COUNT have to be counted from the start.
+fn first_period(rule: &Rule, start: NaiveDate, from: NaiveDate) -> u32 {
+    if rule.count.is_some() || from <= start {
+        return 0;
+    }
+
+    let elapsed = match rule.frequency {
+        Frequency::Daily => (from - start).num_days(),
+        Frequency::Weekly => (from - start).num_weeks(),
+        Frequency::Monthly => {
+            i64::from(from.year() - start.year()) * 12 + i64::from(from.month())
+                - i64::from(start.month())
+        }
+        Frequency::Yearly => i64::from(from.year() - start.year()),
+    };
+    // One period earlier, since `from` is in local time and occurrences may overlap it
+    u32::try_from(elapsed / i64::from(rule.interval))
+        .unwrap_or(u32::MAX)
+        .saturating_sub(1)
+}
+
+/// Dates of a month matching the BYMONTHDAY and BYDAY parts, the day of the start otherwise
+fn month_dates(rule: &Rule, month: NaiveDate, start_day: u32) -> Vec<NaiveDate> {
+    let days_in_month = month
+        .checked_add_months(Months::new(1))
+        .map(|next| (next - month).num_days() as i32)
+        .unwrap_or(31);
+
+    let mut weekday_dates = Vec::new();
+    for (ordinal, weekday) in &rule.by_day {
+        let matching: Vec<NaiveDate> = (0..days_in_month)
+            .map(|offset| month + Duration::days(i64::from(offset)))
+            .filter(|date| date.weekday() == *weekday)
+            .collect();
+        match ordinal {
+            Some(ordinal) if *ordinal > 0 => {
+                weekday_dates.extend(matching.get(*ordinal as usize - 1).copied())
+            }
+            Some(ordinal) if *ordinal < 0 => {
+                let idx = matching.len() as i32 + ordinal;
+                if idx >= 0 {
+                    weekday_dates.push(matching[idx as usize]);
+                }
+            }
+            _ => weekday_dates.extend(matching),
+        }
+    }
+
+    if !rule.by_month_day.is_empty() {
+        return rule
+            .by_month_day
+            .iter()
+            .map(|day| {
+                if *day < 0 {
+                    days_in_month + day + 1
+                } else {
+                    *day
+                }
+            })
+            .filter(|day| (1..=days_in_month).contains(day))
+            .filter_map(|day| month.with_day(day as u32))
+            // Both parts limit the dates, e.g. BYDAY=FR;BYMONTHDAY=13 is only Friday the 13th
+            .filter(|date| rule.by_day.is_empty() || weekday_dates.contains(date))
+            .collect();
+    }
+
+    if !rule.by_day.is_empty() {
+        return weekday_dates;
+    }
+
+    month.with_day(start_day).into_iter().collect()
+}
+
+/// Occurrences of all events in the calendar starting inside the range, sorted by start.
+/// Recurring events are expanded, modified instances replace the occurrence they override.
+pub fn occurrences(
+    calendar: &Calendar,
+    from: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<Occurrence<'_>> {
+    let events: Vec<&Event> = calendar
+        .components
+        .iter()
+        .filter_map(|component| match component {
+            CalendarComponent::Event(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+
+    // Instances of recurring events that were moved or cancelled individually
+    let mut overridden: HashSet<(String, DateTime<Local>)> = HashSet::new();
+    for event in &events {
+        if let (Some(uid), Some(recurrence_id)) =
+            (event.get_uid(), event.properties().get("RECURRENCE-ID"))
+            && let Some(date) = DatePerhapsTime::from_property(recurrence_id)
+        {
+            let (date_time, anchor) = anchor_of(&date);
+            if let Some(instant) = anchor.to_local(date_time) {
+                overridden.insert((uid.to_string(), instant));
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for event in events {
+        result.extend(event_occurrences(
+            event,
+            &overridden,
+            from,
+            Some(until),
+            None,
+        ));
+    }
+
+    result.sort_by_key(|occurrence| occurrence.start);
+    result
+}
+
+/// Whether any occurrence of the event overlaps the range, recurring events are expanded
+pub fn occurs_between(
+    event: &Event,
+    from: DateTime<Local>,
+    until: Option<DateTime<Local>>,
+) -> bool {
+    let Some(start) = event.get_start() else {
+        return true;
+    };
+    let (start, anchor) = anchor_of(&start);
+    let duration = event_duration(event, start, anchor);
+
+    !event_occurrences(event, &HashSet::new(), from - duration, until, Some(1)).is_empty()
+}
+
+fn parse_rule(rule: &str, anchor: Anchor) -> Option<Rule> {
+    let mut parsed = Rule {
+        by_day: Vec::new(),
+        by_month: Vec::new(),
+        by_month_day: Vec::new(),
+        by_set_pos: Vec::new(),
+        count: None,
+        frequency: Frequency::Daily,
+        interval: 1,
+        until: None,
+    };
+    let mut frequency = None;
+
+    for part in rule.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key.to_uppercase().as_str() {
+            "BYDAY" => {
+                for day in value.split(',') {
+                    let split = day.len().checked_sub(2)?;
+                    let weekday = match &day[split..] {
+                        "MO" => Weekday::Mon,
+                        "TU" => Weekday::Tue,
+                        "WE" => Weekday::Wed,
+                        "TH" => Weekday::Thu,
+                        "FR" => Weekday::Fri,
+                        "SA" => Weekday::Sat,
+                        "SU" => Weekday::Sun,
+                        _ => return None,
+                    };
+                    let ordinal = match &day[..split] {
+                        "" => None,
+                        ordinal => Some(ordinal.trim_start_matches('+').parse().ok()?),
+                    };
+                    parsed.by_day.push((ordinal, weekday));
+                }
+            }
+            "BYMONTH" => {
+                for month in value.split(',') {
+                    parsed.by_month.push(month.parse().ok()?);
+                }
+            }
+            "BYMONTHDAY" => {
+                for day in value.split(',') {
+                    parsed.by_month_day.push(day.parse().ok()?);
+                }
+            }
+            "COUNT" => parsed.count = Some(value.parse().ok()?),
+            "FREQ" => {
+                frequency = Some(match value {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    // Sub-daily frequencies are not supported
+                    _ => return None,
+                });
+            }
+            "INTERVAL" => parsed.interval = value.parse::<u32>().ok()?.max(1),
+            "UNTIL" => {
+                let date = DatePerhapsTime::from_property(&Property::new("UNTIL", value)).or_else(
+                    || {
+                        NaiveDate::parse_from_str(value, "%Y%m%d")
+                            .ok()
+                            .map(DatePerhapsTime::Date)
+                    },
+                )?;
+                parsed.until = match date {
+                    // Dates include the whole day
+                    DatePerhapsTime::Date(date) => anchor.to_local(date.and_hms_opt(23, 59, 59)?),
+                    date => {
+                        let (date_time, until_anchor) = anchor_of(&date);
+                        match until_anchor {
+                            Anchor::Floating => anchor.to_local(date_time),
+                            until_anchor => until_anchor.to_local(date_time),
+                        }
+                    }
+                };
+            }
+            "BYSETPOS" => {
+                for pos in value.split(',') {
+                    parsed.by_set_pos.push(pos.parse().ok()?);
+                }
+            }
+            // Ignoring these would expand to wrong occurrences, the rule is not expanded instead
+            key @ ("BYHOUR" | "BYMINUTE" | "BYSECOND" | "BYWEEKNO" | "BYYEARDAY") => {
+                println!(
+                    "[{}] Unsupported {key} in recurrence rule '{rule}', only the first occurrence is used",
+                    Local::now().format("%Y-%m-%dT%H:%M:%S")
+                );
+                return None;
+            }
+            _ => (),
+        }
+    }
+
+    parsed.frequency = frequency?;
+    Some(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar(events: &[&str]) -> Calendar {
+        let events: String = events
+            .iter()
+            .map(|event| format!("BEGIN:VEVENT\r\n{event}\r\nEND:VEVENT\r\n"))
+            .collect();
+        format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{events}END:VCALENDAR\r\n")
+            .parse()
+            .unwrap()
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, 0, 0)
+            .unwrap()
+    }
+
+    fn starts(calendar: &Calendar, from: DateTime<Local>, until: DateTime<Local>) -> Vec<String> {
+        occurrences(calendar, from, until)
+            .iter()
+            .map(|occurrence| occurrence.start.format("%Y-%m-%d %H:%M").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn count_limits_the_occurrences() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250106T100000\r\nDTEND:20250106T110000\r\nRRULE:FREQ=DAILY;COUNT=3",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 2, 1, 0)),
+            vec!["2025-01-06 10:00", "2025-01-07 10:00", "2025-01-08 10:00"]
+        );
+    }
+
+    #[test]
+    fn excluded_dates_count_towards_count() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=DAILY;COUNT=3\r\nEXDATE:20250107T100000",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 2, 1, 0)),
+            vec!["2025-01-06 10:00", "2025-01-08 10:00"]
+        );
+    }
+
+    #[test]
+    fn count_is_counted_from_the_start() {
+        let calendar = calendar(&["UID:a\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=WEEKLY;COUNT=4"]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 20, 0), at(2025, 3, 1, 0)),
+            vec!["2025-01-20 10:00", "2025-01-27 10:00"]
+        );
+    }
+
+    #[test]
+    fn until_date_includes_the_whole_day() {
+        let calendar =
+            calendar(&["UID:a\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=DAILY;UNTIL=20250108"]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 2, 1, 0)),
+            vec!["2025-01-06 10:00", "2025-01-07 10:00", "2025-01-08 10:00"]
+        );
+    }
+
+    #[test]
+    fn comma_separated_exdates_are_excluded() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART;VALUE=DATE:20250106\r\nRRULE:FREQ=DAILY;COUNT=4\r\nEXDATE;VALUE=DATE:20250107,20250108",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 2, 1, 0)),
+            vec!["2025-01-06 00:00", "2025-01-09 00:00"]
+        );
+    }
+
+    #[test]
+    fn monthly_day_is_skipped_in_shorter_months() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250131T100000\r\nRRULE:FREQ=MONTHLY;BYMONTHDAY=31;COUNT=3",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2026, 1, 1, 0)),
+            vec!["2025-01-31 10:00", "2025-03-31 10:00", "2025-05-31 10:00"]
+        );
+    }
+
+    #[test]
+    fn last_weekday_of_the_month() {
+        let calendar =
+            calendar(&["UID:a\r\nDTSTART:20250131T100000\r\nRRULE:FREQ=MONTHLY;BYDAY=-1FR"]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 4, 1, 0)),
+            vec!["2025-01-31 10:00", "2025-02-28 10:00", "2025-03-28 10:00"]
+        );
+    }
+
+    #[test]
+    fn overridden_instances_replace_the_occurrence() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=DAILY;COUNT=3",
+            "UID:a\r\nRECURRENCE-ID:20250107T100000\r\nDTSTART:20250107T150000",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 2, 1, 0)),
+            vec!["2025-01-06 10:00", "2025-01-07 15:00", "2025-01-08 10:00"]
+        );
+    }
+
+    #[test]
+    fn long_running_series_are_expanded_from_the_range() {
+        let calendar = calendar(&["UID:a\r\nDTSTART:18000101T100000\r\nRRULE:FREQ=DAILY"]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 6, 0), at(2025, 1, 8, 0)),
+            vec!["2025-01-06 10:00", "2025-01-07 10:00"]
+        );
+    }
+
+    #[test]
+    fn huge_intervals_end_the_expansion() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=YEARLY;INTERVAL=4294967295",
+            "UID:b\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=DAILY;INTERVAL=4294967295",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(9999, 1, 1, 0)),
+            vec!["2025-01-06 10:00", "2025-01-06 10:00"]
+        );
+    }
+
+    #[test]
+    fn occurs_between_detects_ongoing_series() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20200106T100000\r\nDTEND:20200106T110000\r\nRRULE:FREQ=WEEKLY;UNTIL=20250113",
+        ]);
+        let CalendarComponent::Event(event) = &calendar.components[0] else {
+            panic!("no event");
+        };
+
+        assert!(occurs_between(event, at(2025, 1, 13, 10), None));
+        assert!(!occurs_between(event, at(2025, 1, 14, 0), None));
+    }
+
+    #[test]
+    fn set_position_picks_last_weekday_of_the_month() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250131T100000\r\nRRULE:FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 4, 1, 0)),
+            vec!["2025-01-31 10:00", "2025-02-28 10:00", "2025-03-31 10:00"]
+        );
+    }
+
+    #[test]
+    fn unsupported_rule_parts_are_not_expanded() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250106T100000\r\nRRULE:FREQ=DAILY;BYHOUR=10,16;COUNT=4",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2025, 2, 1, 0)),
+            vec!["2025-01-06 10:00"]
+        );
+    }
+
+    #[test]
+    fn month_day_and_weekday_both_limit_the_dates() {
+        let calendar = calendar(&[
+            "UID:a\r\nDTSTART:20250613T100000\r\nRRULE:FREQ=MONTHLY;BYDAY=FR;BYMONTHDAY=13",
+        ]);
+
+        assert_eq!(
+            starts(&calendar, at(2025, 1, 1, 0), at(2026, 6, 1, 0)),
+            vec!["2025-06-13 10:00", "2026-02-13 10:00", "2026-03-13 10:00"]
+        );
+    }
+}
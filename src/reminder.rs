@@ -0,0 +1,118 @@
+use crate::cache::{is_cached, load_from_cache, save_to_cache};
+use crate::calendar::calendar_name;
+use crate::config::Config;
+use crate::notification::push_reminder_notifications;
+use crate::recurrence::occurrences;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration, Local};
+use icalendar::{Calendar, Component, EventLike};
+use std::collections::HashMap;
+
+static REMINDER_STATE_FILE: &str = "reminders.json";
+
+/// Upcoming occurrence of an event a reminder is due for
+pub struct Reminder {
+    pub all_day: bool,
+    pub calendar_name: String,
+    pub end: DateTime<Local>,
+    pub location: Option<String>,
+    pub start: DateTime<Local>,
+    pub summary: String,
+}
+
+fn load_reminder_state() -> Result<HashMap<String, DateTime<Local>>> {
+    if !is_cached(REMINDER_STATE_FILE) {
+        return Ok(HashMap::new());
+    }
+
+    let data = load_from_cache(REMINDER_STATE_FILE)?;
+    serde_json::from_str(&data).with_context(|| "Failed to parse reminder state!")
+}
+
+/// Sends reminders for all events of the filtered calendars starting within their reminder
+/// period. Every reminder is sent once, sent reminders are tracked until the event started.
+pub fn push_reminders(config: &Config) -> Result<()> {
+    let now = Local::now();
+    let mut state = load_reminder_state()?;
+    state.retain(|_, start| *start > now);
+
+    let mut reminders = Vec::new();
+
+    for calendar in &config.calendars {
+        let Some(reminder_config) = &calendar.reminders else {
+            continue;
+        };
+        let Some(max_minutes) = reminder_config.minutes_before.iter().max() else {
+            continue;
+        };
+
+        let name = calendar_name(calendar)?;
+        let filename = format!("{name}_filtered.ics");
+        if !is_cached(&filename) {
+            continue;
+        }
+        let data = load_from_cache(&filename)?;
+
+        // Not using with_context() because "the trait bound `std::string::String: StdError` is not satisfied"
+        let parsed_calendar: Calendar = match data.parse() {
+            Ok(data) => data,
+            Err(e) => bail!("Failed to parse calendar '{name}'!\n{e}"),
+        };
+
+        let until = now + Duration::minutes(i64::from(*max_minutes)) + Duration::seconds(1);
+        for occurrence in occurrences(&parsed_calendar, now, until) {
+            if occurrence.all_day && !reminder_config.all_day {
+                continue;
+            }
+
+            // Reminders that became due at once, e.g. after a downtime, are sent only once
+            let uid = occurrence.event.get_uid().unwrap_or_default();
+            let keys: Vec<String> = reminder_config
+                .minutes_before
+                .iter()
+                .filter(|minutes| occurrence.start - Duration::minutes(i64::from(**minutes)) <= now)
+                .map(|minutes| format!("{name}|{uid}|{}|{minutes}", occurrence.start.to_rfc3339()))
+                .collect();
+
+            if keys.iter().any(|key| !state.contains_key(key)) {
+                let reminder = Reminder {
+                    all_day: occurrence.all_day,
+                    calendar_name: name.clone(),
+                    end: occurrence.end,
+                    location: occurrence.event.get_location().map(String::from),
+                    start: occurrence.start,
+                    summary: occurrence
+                        .event
+                        .get_summary()
+                        .unwrap_or_default()
+                        .to_string(),
+                };
+                reminders.push((reminder, keys));
+            }
+        }
+    }
+
+    if !reminders.is_empty() {
+        println!(
+            "[{}] Sending {} reminder(s).",
+            now.format("%Y-%m-%dT%H:%M:%S"),
+            reminders.len()
+        );
+        let (reminders, keys): (Vec<Reminder>, Vec<Vec<String>>) = reminders.into_iter().unzip();
+        let delivered = push_reminder_notifications(config, &reminders)?;
+
+        // Reminders that could not be delivered at all are tried again by the next run
+        for ((reminder, keys), delivered) in reminders.iter().zip(keys).zip(delivered) {
+            if delivered {
+                for key in keys {
+                    state.insert(key, reminder.start);
+                }
+            }
+        }
+    }
+
+    let data = serde_json::to_string_pretty(&state)
+        .with_context(|| "Failed to serialize reminder state!")?;
+    save_to_cache(&data, REMINDER_STATE_FILE)
+}
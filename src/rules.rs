@@ -0,0 +1,332 @@
+use crate::cache::{is_cached, load_from_cache, save_to_cache};
+use crate::calendar::calendar_name;
+use crate::config::{CalendarConfig, Config, StaleRulesConfig};
+use crate::notification::push_rule_notifications;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration, Local};
+use icalendar::{Calendar, CalendarComponent, Component};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+static DEFAULT_STALE_AFTER_DAYS: u32 = 30;
+static RULE_STATE_FILE: &str = "rules.json";
+
+/// Black- or whitelist entry that did not match any event for too long
+pub struct StaleRule {
+    pub last_match: Option<DateTime<Local>>,
+    pub rule: String,
+    pub unmatched_runs: u32,
+    pub whitelist: bool,
+}
+
+/// Stale rules of a calendar
+pub struct StaleRules {
+    pub calendar_name: String,
+    pub rules: Vec<StaleRule>,
+}
+
+/// Matches of a black- or whitelist entry across runs
+#[derive(Deserialize, Serialize)]
+struct RuleStats {
+    first_seen: DateTime<Local>,
+    last_match: Option<DateTime<Local>>,
+    matched_events: usize,
+    matched_runs: u32,
+    unmatched_runs: u32,
+    /// Whether the rule was already reported as stale since it last matched
+    warned: bool,
+    whitelist: bool,
+}
+
+type RuleState = BTreeMap<String, BTreeMap<String, RuleStats>>;
+
+/// Rules of the calendar with the entry they match, whitelist entries are flagged
+fn calendar_rules(calendar: &CalendarConfig) -> Vec<(String, &str, bool)> {
+    let blacklist = calendar
+        .blacklist
+        .iter()
+        .flatten()
+        .map(|entry| (entry, false));
+    let whitelist = calendar
+        .whitelist
+        .iter()
+        .flatten()
+        .map(|entry| (entry, true));
+
+    blacklist
+        .chain(whitelist)
+        .map(|(entry, whitelist)| {
+            let kind = if whitelist { "whitelist" } else { "blacklist" };
+            (format!("{kind}: \"{entry}\""), entry.as_str(), whitelist)
+        })
+        .collect()
+}
+
+fn is_stale(stats: &RuleStats, config: Option<&StaleRulesConfig>, now: DateTime<Local>) -> bool {
+    if stats.unmatched_runs == 0 {
+        return false;
+    }
+    // Whitelist entries that never matched are likely typos
+    if stats.whitelist && stats.last_match.is_none() {
+        return true;
+    }
+
+    let (after_days, after_runs) = match config {
+        None
+        | Some(StaleRulesConfig {
+            after_days: None,
+            after_runs: None,
+        }) => (Some(DEFAULT_STALE_AFTER_DAYS), None),
+        Some(config) => (config.after_days, config.after_runs),
+    };
+    let unmatched_since = stats.last_match.unwrap_or(stats.first_seen);
+
+    after_runs.is_some_and(|runs| stats.unmatched_runs >= runs)
+        || after_days.is_some_and(|days| now - unmatched_since >= Duration::days(i64::from(days)))
+}
+
+fn load_rule_state() -> Result<RuleState> {
+    if !is_cached(RULE_STATE_FILE) {
+        return Ok(RuleState::new());
+    }
+
+    let data = load_from_cache(RULE_STATE_FILE)?;
+    serde_json::from_str(&data).with_context(|| "Failed to parse rule statistics!")
+}
+
+/// Prints the match statistics of all rules, stale ones are marked
+pub fn print_rule_status(config: &Config) -> Result<()> {
+    let state = load_rule_state()?;
+    let now = Local::now();
+
+    for calendar in &config.calendars {
+        let name = calendar_name(calendar)?;
+        println!("Calendar '{name}':");
+
+        let Some(rules) = state.get(&name) else {
+            println!("  No statistics yet");
+            continue;
+        };
+
+        for (rule, _, _) in calendar_rules(calendar) {
+            let Some(stats) = rules.get(&rule) else {
+                println!("  {rule}: no statistics yet");
+                continue;
+            };
+
+            let last_match = match stats.last_match {
+                Some(last_match) => last_match.format("%Y-%m-%d %H:%M").to_string(),
+                None => "never".to_string(),
+            };
+            let marker = if is_stale(stats, config.stale_rules.as_ref(), now) {
+                " [stale]"
+            } else {
+                ""
+            };
+            println!(
+                "  {rule}: {} events in the last run, matched in {} runs, last match {last_match}, \
+                 {} runs without match{marker}",
+                stats.matched_events, stats.matched_runs, stats.unmatched_runs
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts the events every rule matches in the cached calendars and, if configured, warns once
+/// about rules that became stale
+pub fn push_rule_warnings(config: &Config) -> Result<()> {
+    let now = Local::now();
+    let mut old_state = load_rule_state()?;
+    let mut state = RuleState::new();
+    let mut warnings = Vec::new();
+
+    for calendar in &config.calendars {
+        let name = calendar_name(calendar)?;
+        let filename = format!("{name}.ics");
+        if !is_cached(&filename) {
+            continue;
+        }
+        let data = load_from_cache(&filename)?;
+
+        // Not using with_context() because "the trait bound `std::string::String: StdError` is not satisfied"
+        let parsed_calendar: Calendar = match data.parse() {
+            Ok(data) => data,
+            Err(e) => bail!("Failed to parse calendar '{name}'!\n{e}"),
+        };
+        let summaries: Vec<&str> = parsed_calendar
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                CalendarComponent::Event(event) => event.get_summary(),
+                _ => None,
+            })
+            .collect();
+
+        let old_rules = old_state.remove(&name).unwrap_or_default();
+        let (rules, stale) = update_rule_stats(
+            calendar,
+            &summaries,
+            old_rules,
+            config.stale_rules.as_ref(),
+            now,
+        );
+
+        if !stale.is_empty() {
+            warnings.push(StaleRules {
+                calendar_name: name.clone(),
+                rules: stale,
+            });
+        }
+        state.insert(name, rules);
+    }
+
+    if !warnings.is_empty() {
+        println!(
+            "[{}] Sending stale rule warnings.",
+            now.format("%Y-%m-%dT%H:%M:%S")
+        );
+        push_rule_notifications(config, &warnings)?;
+    }
+
+    let data = serde_json::to_string_pretty(&state)
+        .with_context(|| "Failed to serialize rule statistics!")?;
+    save_to_cache(&data, RULE_STATE_FILE)
+}
+
+/// Updates the statistics of the rules of a calendar with the summaries of its events, returns
+/// them and the rules that became stale if warnings are configured
+fn update_rule_stats(
+    calendar: &CalendarConfig,
+    summaries: &[&str],
+    mut old_rules: BTreeMap<String, RuleStats>,
+    stale_config: Option<&StaleRulesConfig>,
+    now: DateTime<Local>,
+) -> (BTreeMap<String, RuleStats>, Vec<StaleRule>) {
+    let mut rules = BTreeMap::new();
+    let mut stale = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (rule, entry, whitelist) in calendar_rules(calendar) {
+        if !seen.insert(rule.clone()) {
+            continue;
+        }
+
+        let mut stats = old_rules.remove(&rule).unwrap_or(RuleStats {
+            first_seen: now,
+            last_match: None,
+            matched_events: 0,
+            matched_runs: 0,
+            unmatched_runs: 0,
+            warned: false,
+            whitelist,
+        });
+        stats.matched_events = summaries
+            .iter()
+            .filter(|summary| **summary == entry)
+            .count();
+        if stats.matched_events > 0 {
+            stats.last_match = Some(now);
+            stats.matched_runs += 1;
+            stats.unmatched_runs = 0;
+            stats.warned = false;
+        } else {
+            stats.unmatched_runs += 1;
+        }
+
+        if stale_config.is_some() && !stats.warned && is_stale(&stats, stale_config, now) {
+            stats.warned = true;
+            stale.push(StaleRule {
+                last_match: stats.last_match,
+                rule: rule.clone(),
+                unmatched_runs: stats.unmatched_runs,
+                whitelist,
+            });
+        }
+        rules.insert(rule, stats);
+    }
+
+    (rules, stale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static STALE_AFTER_WEEK: StaleRulesConfig = StaleRulesConfig {
+        after_days: Some(7),
+        after_runs: None,
+    };
+
+    fn calendar(list: &str) -> CalendarConfig {
+        toml::from_str(&format!(
+            "url = \"https://example.org/uni.ics\"\n{list} = [\"Lecture\", \"Lab\"]"
+        ))
+        .unwrap()
+    }
+
+    /// Stale rules of the second run, a week after the first one
+    fn stale_after_a_week(list: &str, first: &[&str], second: &[&str]) -> Vec<String> {
+        let calendar = calendar(list);
+        let start = Local::now() - Duration::days(8);
+        let (rules, _) = update_rule_stats(
+            &calendar,
+            first,
+            BTreeMap::new(),
+            Some(&STALE_AFTER_WEEK),
+            start,
+        );
+        let (_, stale) = update_rule_stats(
+            &calendar,
+            second,
+            rules,
+            Some(&STALE_AFTER_WEEK),
+            Local::now(),
+        );
+
+        stale.into_iter().map(|stale| stale.rule).collect()
+    }
+
+    #[test]
+    fn unmatched_blacklist_entry_becomes_stale() {
+        let stale = stale_after_a_week("blacklist", &["Lecture"], &["Lecture", "Seminar"]);
+
+        assert_eq!(stale, [r#"blacklist: "Lab""#]);
+    }
+
+    #[test]
+    fn unmatched_whitelist_entry_becomes_stale() {
+        let stale = stale_after_a_week("whitelist", &["Lecture", "Lab"], &["Lab"]);
+
+        assert_eq!(stale, [r#"whitelist: "Lecture""#]);
+    }
+
+    #[test]
+    fn matching_entries_are_not_stale() {
+        let stale = stale_after_a_week("blacklist", &["Lecture", "Lab"], &["Lab", "Lecture"]);
+
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn stale_entries_are_reported_once() {
+        let calendar = calendar("blacklist");
+        let start = Local::now() - Duration::days(8);
+        let (rules, _) = update_rule_stats(
+            &calendar,
+            &[],
+            BTreeMap::new(),
+            Some(&STALE_AFTER_WEEK),
+            start,
+        );
+        let (rules, first) =
+            update_rule_stats(&calendar, &[], rules, Some(&STALE_AFTER_WEEK), Local::now());
+        let (_, second) =
+            update_rule_stats(&calendar, &[], rules, Some(&STALE_AFTER_WEEK), Local::now());
+
+        assert_eq!(first.len(), 2);
+        assert!(second.is_empty());
+    }
+}
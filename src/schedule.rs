@@ -0,0 +1,367 @@
+use crate::cache::{is_cached, load_from_cache, save_to_cache};
+use crate::config::{Channel, QuietHoursConfig, ScheduleConfig};
+use crate::diff::{CalendarDiff, events_identical};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveTime};
+use icalendar::{Component, Event};
+use serde::{Deserialize, Serialize};
+
+static SCHEDULE_FILE: &str = "schedule.json";
+
+/// Diffs routed to a channel, grouped by the recipients replacing the ones of the channel config
+pub type RoutedDiffs = Vec<(Option<Vec<String>>, Vec<CalendarDiff>)>;
+
+/// Version of an event before and after its changes, `None` if it did not exist
+type EventChange = (Option<Event>, Option<Event>);
+
+#[derive(Deserialize, Serialize)]
+struct ChannelSchedule {
+    channel: Channel,
+    held: RoutedDiffs,
+    last_delivery: Option<DateTime<Local>>,
+}
+
+/// Changes held back by quiet hours or delivery intervals, persisted between runs
+#[derive(Default, Deserialize, Serialize)]
+pub struct ScheduleState {
+    channels: Vec<ChannelSchedule>,
+}
+
+impl ScheduleState {
+    /// Returns the diffs to deliver now, including previously held back ones. Holds back the
+    /// diffs instead while the channel is in its quiet hours or was notified too recently.
+    pub fn release(
+        &mut self,
+        channel: Channel,
+        config: &ScheduleConfig,
+        routed_diffs: RoutedDiffs,
+    ) -> RoutedDiffs {
+        let idx = match self
+            .channels
+            .iter()
+            .position(|schedule| schedule.channel == channel)
+        {
+            Some(idx) => idx,
+            None => {
+                self.channels.push(ChannelSchedule {
+                    channel,
+                    held: Vec::new(),
+                    last_delivery: None,
+                });
+                self.channels.len() - 1
+            }
+        };
+        let schedule = &mut self.channels[idx];
+
+        let now = Local::now();
+        let quiet = config
+            .quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| in_quiet_hours(quiet_hours, now));
+        let too_early = match (config.min_interval_hours, schedule.last_delivery) {
+            (Some(hours), Some(last_delivery)) => {
+                now - last_delivery < Duration::hours(i64::from(hours))
+            }
+            _ => false,
+        };
+
+        if quiet || too_early {
+            if !routed_diffs.is_empty() {
+                println!(
+                    "[{}] Holding back {channel} notifications.",
+                    now.format("%Y-%m-%dT%H:%M:%S")
+                );
+            }
+            merge_routed_diffs(&mut schedule.held, routed_diffs);
+            return Vec::new();
+        }
+
+        let mut released = std::mem::take(&mut schedule.held);
+        merge_routed_diffs(&mut released, routed_diffs);
+        if !released.is_empty() {
+            schedule.last_delivery = Some(now);
+        }
+
+        released
+    }
+}
+
+fn in_quiet_hours(config: &QuietHoursConfig, now: DateTime<Local>) -> bool {
+    let time: NaiveTime = match config.timezone {
+        Some(timezone) => now.with_timezone(&timezone).time(),
+        None => now.time(),
+    };
+
+    if config.start <= config.end {
+        config.start <= time && time < config.end
+    } else {
+        time >= config.start || time < config.end
+    }
+}
+
+pub fn load_schedule_state() -> Result<ScheduleState> {
+    if !is_cached(SCHEDULE_FILE) {
+        return Ok(ScheduleState::default());
+    }
+
+    let data = load_from_cache(SCHEDULE_FILE)?;
+    serde_json::from_str(&data).with_context(|| "Failed to parse notification schedule!")
+}
+
+/// Combines the changes of a calendar per event, keeping the version before the first and after
+/// the last change. Changes that cancel each other out, like an insertion followed by a deletion,
+/// are dropped.
+fn merge_calendar_diff(held: &mut CalendarDiff, diff: CalendarDiff) {
+    let into_changes = |diff: CalendarDiff| {
+        let deletions = diff.deletions.into_iter().map(|old| (Some(old), None));
+        let insertions = diff.insertions.into_iter().map(|new| (None, Some(new)));
+        let modifications = diff
+            .modifications
+            .into_iter()
+            .map(|(old, new)| (Some(old), Some(new)));
+        deletions.chain(insertions).chain(modifications)
+    };
+
+    let held_diff = std::mem::replace(
+        held,
+        CalendarDiff {
+            calendar_name: held.calendar_name.clone(),
+            ..CalendarDiff::default()
+        },
+    );
+    let mut changes: Vec<EventChange> = into_changes(held_diff).collect();
+    for (old, new) in into_changes(diff) {
+        let uid = old
+            .as_ref()
+            .or(new.as_ref())
+            .and_then(|event| event.get_uid());
+        // Follows the events under their latest UID, which changes for regenerated UIDs
+        let previous = changes.iter_mut().find(|(held_old, held_new)| {
+            uid.is_some()
+                && held_new
+                    .as_ref()
+                    .or(held_old.as_ref())
+                    .and_then(|event| event.get_uid())
+                    == uid
+        });
+
+        match previous {
+            Some((_, held_new)) => *held_new = new,
+            None => changes.push((old, new)),
+        }
+    }
+
+    for change in changes {
+        match change {
+            (Some(old), None) => held.deletions.push(old),
+            (None, Some(new)) => held.insertions.push(new),
+            (Some(old), Some(new)) if !events_identical(&old, &new) => {
+                held.modifications.push((old, new));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Adds the diffs to the group with the same recipients, changes of the same calendar are combined
+fn merge_routed_diffs(target: &mut RoutedDiffs, routed_diffs: RoutedDiffs) {
+    for (recipients, diffs) in routed_diffs {
+        let group = match target.iter().position(|(group, _)| *group == recipients) {
+            Some(idx) => &mut target[idx].1,
+            None => {
+                target.push((recipients, Vec::new()));
+                &mut target.last_mut().unwrap().1
+            }
+        };
+
+        for diff in diffs {
+            match group
+                .iter_mut()
+                .find(|held| held.calendar_name == diff.calendar_name)
+            {
+                Some(held) => merge_calendar_diff(held, diff),
+                None => group.push(diff),
+            }
+        }
+
+        group.retain(|diff| {
+            !(diff.deletions.is_empty()
+                && diff.insertions.is_empty()
+                && diff.modifications.is_empty())
+        });
+    }
+
+    target.retain(|(_, diffs)| !diffs.is_empty());
+}
+
+pub fn save_schedule_state(state: &ScheduleState) -> Result<()> {
+    let data = serde_json::to_string_pretty(state)
+        .with_context(|| "Failed to serialize notification schedule!")?;
+    save_to_cache(&data, SCHEDULE_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn quiet_hours(start: &str, end: &str) -> QuietHoursConfig {
+        QuietHoursConfig {
+            end: end.parse().unwrap(),
+            start: start.parse().unwrap(),
+            timezone: None,
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Local> {
+        let time: NaiveTime = time.parse().unwrap();
+        Local
+            .from_local_datetime(&NaiveDate::from_ymd_opt(2025, 1, 15).unwrap().and_time(time))
+            .unwrap()
+    }
+
+    fn event(uid: &str, summary: &str) -> Event {
+        Event::new().uid(uid).summary(summary).done()
+    }
+
+    fn diff(
+        deletions: Vec<Event>,
+        insertions: Vec<Event>,
+        modifications: Vec<(Event, Event)>,
+    ) -> RoutedDiffs {
+        vec![(
+            None,
+            vec![CalendarDiff {
+                calendar_name: "Work".to_string(),
+                deletions,
+                insertions,
+                modifications,
+            }],
+        )]
+    }
+
+    fn summaries(events: &[Event]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|event| event.get_summary())
+            .collect()
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let config = quiet_hours("12:00", "14:00");
+
+        assert!(in_quiet_hours(&config, at("12:00")));
+        assert!(in_quiet_hours(&config, at("13:59")));
+        assert!(!in_quiet_hours(&config, at("14:00")));
+        assert!(!in_quiet_hours(&config, at("11:59")));
+    }
+
+    #[test]
+    fn quiet_hours_spanning_midnight() {
+        let config = quiet_hours("22:00", "07:00");
+
+        assert!(in_quiet_hours(&config, at("22:00")));
+        assert!(in_quiet_hours(&config, at("23:59")));
+        assert!(in_quiet_hours(&config, at("00:00")));
+        assert!(in_quiet_hours(&config, at("06:59")));
+        assert!(!in_quiet_hours(&config, at("07:00")));
+        assert!(!in_quiet_hours(&config, at("21:59")));
+        assert!(!in_quiet_hours(&config, at("12:00")));
+    }
+
+    #[test]
+    fn insertion_followed_by_deletion_cancels_out() {
+        let mut held = diff(vec![], vec![event("a", "Meeting")], vec![]);
+
+        merge_routed_diffs(&mut held, diff(vec![event("a", "Meeting")], vec![], vec![]));
+
+        assert!(held.is_empty());
+    }
+
+    #[test]
+    fn modifications_keep_the_first_old_and_the_last_new_version() {
+        let mut held = diff(
+            vec![],
+            vec![],
+            vec![(event("a", "First"), event("a", "Second"))],
+        );
+
+        merge_routed_diffs(
+            &mut held,
+            diff(
+                vec![],
+                vec![],
+                vec![(event("a", "Second"), event("a", "Third"))],
+            ),
+        );
+
+        let modifications = &held[0].1[0].modifications;
+        assert_eq!(modifications.len(), 1);
+        assert_eq!(modifications[0].0.get_summary(), Some("First"));
+        assert_eq!(modifications[0].1.get_summary(), Some("Third"));
+    }
+
+    #[test]
+    fn insertion_followed_by_modification_stays_an_insertion() {
+        let mut held = diff(vec![], vec![event("a", "First")], vec![]);
+
+        merge_routed_diffs(
+            &mut held,
+            diff(
+                vec![],
+                vec![],
+                vec![(event("a", "First"), event("a", "Second"))],
+            ),
+        );
+
+        let diff = &held[0].1[0];
+        assert_eq!(summaries(&diff.insertions), vec!["Second"]);
+        assert!(diff.modifications.is_empty());
+    }
+
+    #[test]
+    fn deletion_followed_by_insertion_becomes_a_modification() {
+        let mut held = diff(vec![event("a", "First")], vec![], vec![]);
+
+        merge_routed_diffs(&mut held, diff(vec![], vec![event("a", "Second")], vec![]));
+
+        let diff = &held[0].1[0];
+        assert!(diff.deletions.is_empty());
+        assert!(diff.insertions.is_empty());
+        assert_eq!(diff.modifications.len(), 1);
+    }
+
+    #[test]
+    fn reverted_modification_cancels_out() {
+        let mut held = diff(
+            vec![],
+            vec![],
+            vec![(event("a", "First"), event("a", "Second"))],
+        );
+
+        merge_routed_diffs(
+            &mut held,
+            diff(
+                vec![],
+                vec![],
+                vec![(event("a", "Second"), event("a", "First"))],
+            ),
+        );
+
+        assert!(held.is_empty());
+    }
+
+    #[test]
+    fn changes_of_different_events_are_kept() {
+        let mut held = diff(vec![], vec![event("a", "First")], vec![]);
+
+        merge_routed_diffs(&mut held, diff(vec![event("b", "Other")], vec![], vec![]));
+
+        let diff = &held[0].1[0];
+        assert_eq!(diff.calendar_name, "Work");
+        assert_eq!(summaries(&diff.insertions), vec!["First"]);
+        assert_eq!(summaries(&diff.deletions), vec!["Other"]);
+    }
+}